@@ -1,5 +1,5 @@
 use specs::{Read, ReadStorage, RunNow, System, World, WorldExt, Write};
-use gameshell_base::{AudioContext, get_keys_text, ImageResource, InputQueue, Keys, MainLoop, Position, Renderable, RenderContext, RenderingHelper, SCREEN_HEIGHT, SoundResource, Time};
+use gameshell_base::{Animation, AudioContext, GameConfig, get_keys_text, ImageResource, InputQueue, Keys, MainLoop, Position, Renderable, RenderContext, RenderingHelper, SCREEN_HEIGHT, SoundKind, SoundResource, Time, Transform};
 
 pub const TILE_WIDTH: i32 = 24;
 pub const MAP_OFFSET_X: i32 = 12;
@@ -84,6 +84,10 @@ impl<'a> SoundResource<'a> for Sound {
         let ref sounds_raw = sound_context::SOUNDS;
         sounds_raw.get(audio_id).unwrap()
     }
+
+    fn get_sound_kind(&self, _audio_id: &Self::AudioId) -> SoundKind {
+        SoundKind::Effect
+    }
 }
 
 #[derive(Default)]
@@ -138,7 +142,9 @@ impl<'a> System<'a> for RenderingSystem<'a> {
         Read<'a, Time>,
         Read<'a, Gameplay>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Transform>,
         ReadStorage<'a, Renderable<Image>>,
+        ReadStorage<'a, Animation>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -146,9 +152,11 @@ impl<'a> System<'a> for RenderingSystem<'a> {
             time,
             gameplay,
             positions,
+            transforms,
             renderables,
+            animations,
         ) = data;
-        self.helper.draw_renderables(&positions, &renderables, &time);
+        self.helper.draw_renderables_ex(&positions, &transforms, &renderables, &animations, &time);
         self.helper.context.draw_bg(TextureId::HelloWorld);
         self.helper.context.draw_text(format!("FPS:  {:.2}", time.fps_avg), TEXT_OFFSET_X, 16);
         self.helper.context.draw_text(format!("TIME: {:>02}:{:>02}", time.minutes, time.seconds), TEXT_OFFSET_X, 30);
@@ -300,5 +308,9 @@ pub fn main() {
     let main_loop = Loop {};
     let image = Image {};
     let sound = Sound {};
-    gameshell_base::run(main_loop, image, sound);
+    let config = GameConfig::new().title("simple");
+    if let Err(error) = gameshell_base::run(main_loop, image, sound, config) {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use sdl2::rect::Rect;
+
+// one sub-rectangle of an image, in pixels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtlasFrame {
+    pub name: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasFrame {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        AtlasFrame { name: None, x, y, width, height }
+    }
+
+    pub fn named(name: &str, x: i32, y: i32, width: u32, height: u32) -> Self {
+        AtlasFrame { name: Some(name.to_string()), x, y, width, height }
+    }
+
+    // frames of a uniform sprite sheet, row by row.
+    pub fn grid(columns: u32, rows: u32, frame_width: u32, frame_height: u32) -> Vec<AtlasFrame> {
+        (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .map(|(column, row)| AtlasFrame::new(
+                (column * frame_width) as i32, (row * frame_height) as i32, frame_width, frame_height))
+            .collect()
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+pub struct Atlas {
+    pub frames: Vec<AtlasFrame>,
+    names: HashMap<String, usize>,
+}
+
+impl Atlas {
+    pub fn new(frames: Vec<AtlasFrame>) -> Self {
+        let names = frames
+            .iter()
+            .enumerate()
+            .filter_map(|(index, frame)| frame.name.clone().map(|name| (name, index)))
+            .collect();
+        Atlas { frames, names }
+    }
+
+    pub fn frame(&self, index: usize) -> Option<&AtlasFrame> {
+        self.frames.get(index)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.get(name).cloned()
+    }
+}
@@ -1,45 +1,379 @@
 extern crate sdl2;
 
 use std::collections::HashMap;
-use sdl2::mixer::{AUDIO_S16LSB, DEFAULT_CHANNELS, InitFlag, Music};
-use crate::SoundResource;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use sdl2::mixer::{AUDIO_S16LSB, Channel, Chunk, Group, LoaderRWops, MAX_VOLUME, Music, Sdl2MixerContext};
+use sdl2::rwops::RWops;
+use specs::{Entity, World, WorldExt};
+use crate::audio_format::{AudioFormat, decoder_flags};
+use crate::camera::entity_position;
+use crate::config::GameConfig;
+use crate::error::{Error, Result};
+use crate::listener::{AudioListener, listener_position};
+use crate::volume::VolumeSettings;
+use crate::{ImageResource, SoundResource};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SoundKind {
+    // decoded up front and played on one of the mixer channels, so several
+    // can overlap each other and the music.
+    Effect,
+    // streamed while playing. only one track plays at a time.
+    #[default]
+    Music,
+}
 
 pub struct AudioContext<'a, SR: SoundResource<'a>> {
-    pub sounds: HashMap<SR::AudioId, Music<'static>>,
+    pub music: HashMap<SR::AudioId, Music<'static>>,
+    pub effects: HashMap<SR::AudioId, Chunk>,
     pub resource: SR,
+    current_music: Option<SR::AudioId>,
+    // plays of the loop section left after the current one, -1 for forever.
+    music_loops_left: i32,
+    // set when the music was stopped or faded out on purpose, so update
+    // does not loop it again.
+    music_stopped: bool,
+    // track, loops and fade-in time to start once the current one has faded out.
+    next_music: Option<(SR::AudioId, i32, i32)>,
+    volume: VolumeSettings,
+    // per-sound volume, multiplied with its bus.
+    sound_volumes: HashMap<SR::AudioId, f32>,
+    settings_path: Option<PathBuf>,
+    // unloads the decoders when dropped, so it must outlive the sounds above.
+    // None when no decoder feature is enabled and only WAV is played.
+    _mixer_context: Option<Sdl2MixerContext>,
+}
+
+// set by the mixer when a track ends. the hook runs on the audio thread,
+// where mixer functions must not be called, so update restarts the music.
+static MUSIC_FINISHED: AtomicBool = AtomicBool::new(false);
+
+fn music_finished() {
+    MUSIC_FINISHED.store(true, Ordering::SeqCst);
+}
+
+fn mixer_volume(level: f32) -> i32 {
+    (level.clamp(0.0, 1.0) * MAX_VOLUME as f32).round() as i32
 }
 
 impl<'a, SR: SoundResource<'a>> AudioContext<'a, SR> {
+    // the play and music functions ignore errors so a busy audio device
+    // never stops the game. use the try_ variants to handle them.
+
+    // plays an effect, or a music track once.
     pub fn play_sound(
         &mut self,
         id: SR::AudioId) {
-        self.sounds
-            .get(&id)
-            .unwrap()
-            .play(1)
-            .expect("TODO: panic message");
+        let _ = self.try_play_sound(id);
+    }
+
+    pub fn try_play_sound(
+        &mut self,
+        id: SR::AudioId) -> Result<()> {
+        if self.effects.contains_key(&id) {
+            self.try_play_effect(id).map(|_| ())
+        } else {
+            self.try_play_music(id, 1)
+        }
+    }
+
+    pub fn play_effect(&mut self, id: SR::AudioId) {
+        let _ = self.try_play_effect(id);
+    }
+
+    // plays on the first free mixer channel and returns it.
+    pub fn try_play_effect(&mut self, id: SR::AudioId) -> Result<Channel> {
+        self.play_effect_panned(id, 255, 255)
+    }
+
+    // plays an effect located at x, y in world pixels, panned and attenuated
+    // by the AudioListener of the world. centered when the listener is disabled.
+    // resource places a listener entity that only has a Position.
+    pub fn play_effect_at<IR: ImageResource>(&mut self, world: &World, resource: &IR, id: SR::AudioId, x: f32, y: f32) {
+        let _ = self.try_play_effect_at(world, resource, id, x, y);
+    }
+
+    pub fn try_play_effect_at<IR: ImageResource>(&mut self, world: &World, resource: &IR, id: SR::AudioId, x: f32, y: f32) -> Result<Channel> {
+        let listener = *world.read_resource::<AudioListener>();
+        if !listener.enabled {
+            return self.try_play_effect(id);
+        }
+        let (left, right) = listener.panning(listener_position(world, &listener, resource), (x, y));
+        self.play_effect_panned(id, left, right)
+    }
+
+    // plays an effect from the Transform or Position of the entity.
+    pub fn play_effect_from<IR: ImageResource>(&mut self, world: &World, resource: &IR, id: SR::AudioId, entity: Entity) {
+        let _ = self.try_play_effect_from(world, resource, id, entity);
+    }
+
+    pub fn try_play_effect_from<IR: ImageResource>(&mut self, world: &World, resource: &IR, id: SR::AudioId, entity: Entity) -> Result<Channel> {
+        match entity_position(world, entity, |position| resource.get_tile_position(position)) {
+            Some((x, y)) => self.try_play_effect_at(world, resource, id, x, y),
+            None => self.try_play_effect(id),
+        }
+    }
+
+    pub fn stop_effects(&mut self) {
+        Channel::all().halt();
+    }
+
+    // loops is the number of times to play the track, -1 to repeat forever.
+    // tracks with a loop start play their intro once and then repeat from it.
+    pub fn play_music(&mut self, id: SR::AudioId, loops: i32) {
+        let _ = self.try_play_music(id, loops);
+    }
+
+    pub fn try_play_music(&mut self, id: SR::AudioId, loops: i32) -> Result<()> {
+        self.start_music(id, loops, 0)
+    }
+
+    pub fn fade_in_music(&mut self, id: SR::AudioId, loops: i32, ms: i32) {
+        let _ = self.try_fade_in_music(id, loops, ms);
+    }
+
+    pub fn try_fade_in_music(&mut self, id: SR::AudioId, loops: i32, ms: i32) -> Result<()> {
+        self.start_music(id, loops, ms)
+    }
+
+    pub fn fade_out_music(&mut self, ms: i32) {
+        let _ = self.try_fade_out_music(ms);
+    }
+
+    pub fn try_fade_out_music(&mut self, ms: i32) -> Result<()> {
+        self.music_stopped = true;
+        self.next_music = None;
+        Music::fade_out(ms).map_err(Error::Audio)
+    }
+
+    // switches tracks with a fade out and then a fade in, not a crossfade:
+    // the mixer streams one track at a time, so the current track fades out
+    // over the first half of ms and the new one fades in over the second,
+    // starting on the first update after the fade out ends.
+    pub fn fade_to_music(&mut self, id: SR::AudioId, loops: i32, ms: i32) {
+        let _ = self.try_fade_to_music(id, loops, ms);
+    }
+
+    pub fn try_fade_to_music(&mut self, id: SR::AudioId, loops: i32, ms: i32) -> Result<()> {
+        if !self.music.contains_key(&id) {
+            return Err(Error::SoundNotFound);
+        }
+        if !Music::is_playing() || Music::is_paused() {
+            return self.start_music(id, loops, ms);
+        }
+        self.try_fade_out_music(ms / 2)?;
+        self.next_music = Some((id, loops, ms / 2));
+        Ok(())
+    }
+
+    pub fn pause_music(&mut self) {
+        Music::pause();
+    }
+
+    pub fn resume_music(&mut self) {
+        Music::resume();
+    }
+
+    pub fn stop_music(&mut self) {
+        self.music_stopped = true;
+        self.next_music = None;
+        Music::halt();
+        self.current_music = None;
+    }
+
+    pub fn is_music_playing(&self) -> bool {
+        Music::is_playing()
+    }
+
+    pub fn is_music_paused(&self) -> bool {
+        Music::is_paused()
+    }
+
+    // the last track started, even if it has finished since.
+    pub fn current_music(&self) -> Option<&SR::AudioId> {
+        self.current_music.as_ref()
+    }
+
+    // starts queued tracks and loops tracks with a loop start once the mixer
+    // reports the current track finished. called once a frame, so the loop
+    // point has a short gap of up to a frame plus the seek.
+    pub fn update(&mut self) {
+        if !MUSIC_FINISHED.swap(false, Ordering::SeqCst) {
+            return;
+        }
+
+        if let Some((id, loops, ms)) = self.next_music.take() {
+            let _ = self.start_music(id, loops, ms);
+            return;
+        }
+
+        if self.music_stopped || self.music_loops_left == 0 {
+            return;
+        }
+        let (id, loop_start) = match self.current_music.clone().and_then(|id| self.resource.get_loop_start(&id).map(|start| (id, start))) {
+            Some(current) => current,
+            None => return,
+        };
+        if let Some(music) = self.music.get(&id) {
+            if music.fade_in_from_pos(1, 0, loop_start).is_ok() && self.music_loops_left > 0 {
+                self.music_loops_left -= 1;
+            }
+        }
+    }
+
+    pub fn sound_format(&self, id: &SR::AudioId) -> Option<AudioFormat> {
+        AudioFormat::detect(self.resource.get_audio(id))
+    }
+
+    pub fn volume_settings(&self) -> &VolumeSettings {
+        &self.volume
+    }
+
+    pub fn set_volume_settings(&mut self, settings: VolumeSettings) {
+        self.volume = settings;
+        self.apply_music_volume();
+        self.apply_effect_volumes();
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.set_volume_settings(VolumeSettings { master: volume.clamp(0.0, 1.0), ..self.volume });
+    }
+
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.set_volume_settings(VolumeSettings { music: volume.clamp(0.0, 1.0), ..self.volume });
+    }
+
+    pub fn set_effects_volume(&mut self, volume: f32) {
+        self.set_volume_settings(VolumeSettings { effects: volume.clamp(0.0, 1.0), ..self.volume });
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.set_volume_settings(VolumeSettings { muted, ..self.volume });
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.set_muted(!self.volume.muted);
+    }
+
+    // volume of one sound relative to its bus, for mixing loud and quiet assets.
+    pub fn set_sound_volume(&mut self, id: SR::AudioId, volume: f32) {
+        self.sound_volumes.insert(id, volume.clamp(0.0, 1.0));
+        self.apply_music_volume();
+        self.apply_effect_volumes();
+    }
+
+    pub fn sound_volume(&self, id: &SR::AudioId) -> f32 {
+        self.sound_volumes.get(id).cloned().unwrap_or(1.0)
+    }
+
+    // writes the settings to the file set with GameConfig::volume_settings.
+    // run calls this when the game ends.
+    pub fn save_volume_settings(&self) -> Result<()> {
+        match &self.settings_path {
+            Some(path) => self.volume.save(path),
+            None => Ok(()),
+        }
+    }
+
+    fn play_effect_panned(&mut self, id: SR::AudioId, left: u8, right: u8) -> Result<Channel> {
+        let chunk = self.effects.get(&id).ok_or(Error::SoundNotFound)?;
+        // panning is set before playing, as it stays on the channel.
+        let channel = Group::default()
+            .find_available()
+            .ok_or_else(|| Error::Audio("no free mixer channel".to_string()))?;
+        channel.set_panning(left, right).map_err(Error::Audio)?;
+        channel.play(chunk, 0).map_err(Error::Audio)
+    }
+
+    fn start_music(&mut self, id: SR::AudioId, loops: i32, fade_ms: i32) -> Result<()> {
+        let music = self.music.get(&id).ok_or(Error::SoundNotFound)?;
+        // with a loop start the mixer plays the track once and update does the looping.
+        let has_loop_start = self.resource.get_loop_start(&id).is_some();
+        let mixer_loops = if has_loop_start && loops != 0 { 1 } else { loops };
+        if fade_ms > 0 {
+            music.fade_in(mixer_loops, fade_ms)
+        } else {
+            music.play(mixer_loops)
+        }.map_err(Error::Audio)?;
+        // halting the previous track runs the hook, which is not a finish of this one.
+        MUSIC_FINISHED.store(false, Ordering::SeqCst);
+
+        self.music_loops_left = match (has_loop_start, loops) {
+            (false, _) => 0,
+            (true, loops) if loops < 0 => -1,
+            (true, loops) => (loops - 1).max(0),
+        };
+        self.music_stopped = false;
+        self.next_music = None;
+        self.current_music = Some(id);
+        self.apply_music_volume();
+        Ok(())
+    }
+
+    fn apply_music_volume(&mut self) {
+        let track_volume = self.current_music.as_ref().map_or(1.0, |id| self.sound_volume(id));
+        Music::set_volume(mixer_volume(self.volume.music_level() * track_volume));
+    }
+
+    fn apply_effect_volumes(&mut self) {
+        let level = self.volume.effects_level();
+        for (id, chunk) in self.effects.iter_mut() {
+            let sound_volume = self.sound_volumes.get(id).cloned().unwrap_or(1.0);
+            chunk.set_volume(mixer_volume(level * sound_volume));
+        }
     }
 }
 
-pub(crate) fn initialize_sounds<'a, SR: SoundResource<'a> + Default + 'static + Copy>(resource: SR) -> AudioContext<'a, SR> {
-    let frequency = 44_100;
+pub(crate) fn initialize_sounds<'a, SR: SoundResource<'a> + Default + 'static + Copy>(resource: SR, config: &GameConfig) -> Result<AudioContext<'a, SR>> {
     let format = AUDIO_S16LSB; // signed 16 bit samples, in little-endian byte order
-    let channels = DEFAULT_CHANNELS; // Stereo
-    let chunk_size = 1_024;
-    sdl2::mixer::open_audio(frequency, format, channels, chunk_size).unwrap();
-    let _mixer_context = sdl2::mixer::init(InitFlag::MP3).unwrap();
-    sdl2::mixer::allocate_channels(4);
+    sdl2::mixer::open_audio(config.audio_frequency, format, config.audio_channels, config.audio_chunk_size).map_err(Error::Audio)?;
+    // sdl2::mixer::init fails for an empty flag set, and WAV needs no decoder.
+    let flags = decoder_flags();
+    let mixer_context = if flags.is_empty() {
+        None
+    } else {
+        Some(sdl2::mixer::init(flags).map_err(Error::Audio)?)
+    };
+    sdl2::mixer::allocate_channels(config.mixing_channels);
+    Music::hook_finished(music_finished);
 
     let mut context = AudioContext {
-        sounds: HashMap::new(),
+        music: HashMap::new(),
+        effects: HashMap::new(),
         resource,
+        current_music: None,
+        music_loops_left: 0,
+        music_stopped: false,
+        next_music: None,
+        volume: config.volume_settings.as_ref().map(VolumeSettings::load_or_default).unwrap_or_default(),
+        sound_volumes: HashMap::new(),
+        settings_path: config.volume_settings.clone(),
+        _mixer_context: mixer_context,
     };
 
     for audio_id in resource.get_audio_ids() {
         let raw = context.resource.get_audio(&audio_id);
-        let music = Music::from_static_bytes(raw).unwrap();
-        context.sounds.insert(audio_id, music);
+        // the mixer reports a disabled decoder as a vague load error. formats
+        // not detected here are left to the mixer.
+        if let Some(format) = AudioFormat::detect(raw).filter(|format| !format.is_enabled()) {
+            return Err(Error::Audio(
+                format!("{:?} support is disabled, enable the `{}` feature", format, format.feature())));
+        }
+        match context.resource.get_sound_kind(&audio_id) {
+            SoundKind::Effect => {
+                // Mix_LoadWAV_RW decodes every format the mixer supports, not only WAV.
+                let chunk = RWops::from_bytes(raw).and_then(|rwops| rwops.load_wav()).map_err(Error::Audio)?;
+                context.effects.insert(audio_id, chunk);
+            },
+            SoundKind::Music => {
+                let music = Music::from_static_bytes(raw).map_err(Error::Audio)?;
+                context.music.insert(audio_id, music);
+            },
+        }
     }
+    context.apply_music_volume();
+    context.apply_effect_volumes();
 
-    context
-}
\ No newline at end of file
+    Ok(context)
+}
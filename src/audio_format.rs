@@ -0,0 +1,91 @@
+use sdl2::mixer::InitFlag;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioFormat {
+    Wav,
+    Ogg,
+    Flac,
+    Mp3,
+    // tracker modules, decoded by the mixer's MOD support.
+    Mod,
+    Xm,
+    S3m,
+    It,
+}
+
+const MOD_TAGS: [&[u8; 4]; 8] = [b"M.K.", b"M!K!", b"M&K!", b"FLT4", b"FLT8", b"4CHN", b"6CHN", b"8CHN"];
+
+impl AudioFormat {
+    // guesses the format from the first bytes of the file.
+    pub fn detect(data: &[u8]) -> Option<AudioFormat> {
+        let at = |offset: usize, tag: &[u8]| data.get(offset..offset + tag.len()) == Some(tag);
+
+        if at(0, b"RIFF") && at(8, b"WAVE") {
+            Some(AudioFormat::Wav)
+        } else if at(0, b"OggS") {
+            Some(AudioFormat::Ogg)
+        } else if at(0, b"fLaC") {
+            Some(AudioFormat::Flac)
+        } else if at(0, b"Extended Module: ") {
+            Some(AudioFormat::Xm)
+        } else if at(0, b"IMPM") {
+            Some(AudioFormat::It)
+        } else if at(44, b"SCRM") {
+            Some(AudioFormat::S3m)
+        } else if MOD_TAGS.iter().any(|tag| at(1080, *tag)) || Self::is_mod_channel_tag(data.get(1080..1084)) {
+            Some(AudioFormat::Mod)
+        } else if at(0, b"ID3") || (data.len() > 1 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0) {
+            Some(AudioFormat::Mp3)
+        } else {
+            None
+        }
+    }
+
+    // `xxCH` and `xxCN` tags of modules with 10 or more channels.
+    fn is_mod_channel_tag(tag: Option<&[u8]>) -> bool {
+        match tag {
+            Some([tens, ones, b'C', b'H' | b'N']) => tens.is_ascii_digit() && ones.is_ascii_digit(),
+            _ => false,
+        }
+    }
+
+    // cargo feature that enables the decoder.
+    pub fn feature(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Mod | AudioFormat::Xm | AudioFormat::S3m | AudioFormat::It => "tracker",
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            // the mixer always decodes WAV.
+            AudioFormat::Wav => true,
+            AudioFormat::Ogg => cfg!(feature = "ogg"),
+            AudioFormat::Flac => cfg!(feature = "flac"),
+            AudioFormat::Mp3 => cfg!(feature = "mp3"),
+            AudioFormat::Mod | AudioFormat::Xm | AudioFormat::S3m | AudioFormat::It => cfg!(feature = "tracker"),
+        }
+    }
+}
+
+// decoders to load in sdl2::mixer::init, one per enabled feature.
+pub(crate) fn decoder_flags() -> InitFlag {
+    let mut flags = InitFlag::empty();
+    if cfg!(feature = "ogg") {
+        flags |= InitFlag::OGG;
+    }
+    if cfg!(feature = "flac") {
+        flags |= InitFlag::FLAC;
+    }
+    if cfg!(feature = "mp3") {
+        flags |= InitFlag::MP3;
+    }
+    if cfg!(feature = "tracker") {
+        flags |= InitFlag::MOD;
+    }
+    flags
+}
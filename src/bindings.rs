@@ -0,0 +1,313 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use sdl2::controller::{Axis, Button};
+use sdl2::keyboard::Keycode;
+use crate::error::Result;
+use crate::input::Keys;
+use crate::settings_file::parse_settings;
+
+const DEFAULT_AXIS_DEADZONE: i16 = 8_000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PhysicalInput {
+    Key(Keycode),
+    ControllerButton(Button),
+    // a stick pushed past the deadzone, true for the positive direction.
+    ControllerAxis(Axis, bool),
+    // buttons and axes of devices SDL has no game controller mapping for.
+    JoyButton(u8),
+    JoyAxis(u8, bool),
+    JoyHat(u8, Keys),
+}
+
+impl PhysicalInput {
+    fn name(&self) -> Option<String> {
+        match self {
+            PhysicalInput::Key(keycode) => Some(keycode.name()),
+            PhysicalInput::ControllerButton(button) => Some(format!("pad:{}", button.string())),
+            PhysicalInput::JoyButton(index) => Some(format!("joy:{}", index)),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<PhysicalInput> {
+        if let Some(button) = name.strip_prefix("pad:") {
+            Button::from_string(button).map(PhysicalInput::ControllerButton)
+        } else if let Some(index) = name.strip_prefix("joy:") {
+            index.parse().ok().map(PhysicalInput::JoyButton)
+        } else {
+            Keycode::from_name(name).map(PhysicalInput::Key)
+        }
+    }
+}
+
+fn quote_name(name: &str) -> String {
+    if name.contains(',') || name.starts_with('"') {
+        format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        name.to_string()
+    }
+}
+
+// splits a comma-separated list of input names, unquoting the names written
+// by quote_name. \" and \\ escape a quote and a backslash inside quotes.
+fn split_names(text: &str) -> std::result::Result<Vec<String>, &'static str> {
+    let mut names = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    loop {
+        while chars.next_if(|char| char.is_whitespace()).is_some() {}
+        let name = if chars.next_if_eq(&'"').is_some() {
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => name.push(chars.next().ok_or("unterminated quote")?),
+                    Some(char) => name.push(char),
+                    None => return Err("unterminated quote"),
+                }
+            }
+            while chars.next_if(|char| char.is_whitespace()).is_some() {}
+            if chars.peek().is_some_and(|char| *char != ',') {
+                return Err("expected `,` after a quoted name");
+            }
+            name
+        } else {
+            let mut name = String::new();
+            while let Some(char) = chars.next_if(|char| *char != ',') {
+                name.push(char);
+            }
+            name.trim_end().to_string()
+        };
+
+        if !name.is_empty() {
+            names.push(name);
+        }
+        // skips the comma, if any.
+        if chars.next().is_none() {
+            break;
+        }
+    }
+
+    Ok(names)
+}
+
+// maps physical keys and buttons onto Keys. stored as a world resource, so it
+// can be changed at runtime, e.g. from an options screen. sticks, joystick
+// axes and hats always map to the directions.
+//
+// the file format is one line per key, listing SDL key names, game
+// controller buttons as pad:<name> and other joystick buttons as joy:<index>.
+// names holding a comma, like the comma key, are written in double quotes:
+//
+//     # comment
+//     A = J, Z, pad:a, joy:0
+//     Start = Return, pad:start, ","
+//     Deadzone = 8000
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    inputs: HashMap<PhysicalInput, Keys>,
+    pub axis_deadzone: i16,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            inputs: [
+                (PhysicalInput::Key(Keycode::Up),       Keys::Up),
+                (PhysicalInput::Key(Keycode::Down),     Keys::Down),
+                (PhysicalInput::Key(Keycode::Left),     Keys::Left),
+                (PhysicalInput::Key(Keycode::Right),    Keys::Right),
+                (PhysicalInput::Key(Keycode::U),        Keys::X),
+                (PhysicalInput::Key(Keycode::I),        Keys::Y),
+                (PhysicalInput::Key(Keycode::J),        Keys::A),
+                (PhysicalInput::Key(Keycode::K),        Keys::B),
+                (PhysicalInput::Key(Keycode::Space),    Keys::Select),
+                (PhysicalInput::Key(Keycode::Return),   Keys::Start),
+
+                (PhysicalInput::ControllerButton(Button::DPadUp),       Keys::Up),
+                (PhysicalInput::ControllerButton(Button::DPadDown),     Keys::Down),
+                (PhysicalInput::ControllerButton(Button::DPadLeft),     Keys::Left),
+                (PhysicalInput::ControllerButton(Button::DPadRight),    Keys::Right),
+                (PhysicalInput::ControllerButton(Button::X),            Keys::X),
+                (PhysicalInput::ControllerButton(Button::Y),            Keys::Y),
+                (PhysicalInput::ControllerButton(Button::A),            Keys::A),
+                (PhysicalInput::ControllerButton(Button::B),            Keys::B),
+                (PhysicalInput::ControllerButton(Button::Back),         Keys::Select),
+                (PhysicalInput::ControllerButton(Button::Start),        Keys::Start),
+
+                (PhysicalInput::JoyButton(0),   Keys::A),
+                (PhysicalInput::JoyButton(1),   Keys::B),
+                (PhysicalInput::JoyButton(2),   Keys::X),
+                (PhysicalInput::JoyButton(3),   Keys::Y),
+                (PhysicalInput::JoyButton(6),   Keys::Select),
+                (PhysicalInput::JoyButton(7),   Keys::Start),
+            ].into_iter().collect(),
+            axis_deadzone: DEFAULT_AXIS_DEADZONE,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn empty() -> Self {
+        KeyBindings {
+            inputs: HashMap::new(),
+            axis_deadzone: DEFAULT_AXIS_DEADZONE,
+        }
+    }
+
+    pub fn get(&self, keycode: &Keycode) -> Option<Keys> {
+        self.get_input(&PhysicalInput::Key(*keycode))
+    }
+
+    pub fn get_input(&self, input: &PhysicalInput) -> Option<Keys> {
+        match input {
+            PhysicalInput::ControllerAxis(Axis::LeftX, false) | PhysicalInput::JoyAxis(0, false) => Some(Keys::Left),
+            PhysicalInput::ControllerAxis(Axis::LeftX, true) | PhysicalInput::JoyAxis(0, true) => Some(Keys::Right),
+            PhysicalInput::ControllerAxis(Axis::LeftY, false) | PhysicalInput::JoyAxis(1, false) => Some(Keys::Up),
+            PhysicalInput::ControllerAxis(Axis::LeftY, true) | PhysicalInput::JoyAxis(1, true) => Some(Keys::Down),
+            PhysicalInput::ControllerAxis(..) | PhysicalInput::JoyAxis(..) => None,
+            PhysicalInput::JoyHat(_, key) => Some(*key),
+            _ => self.inputs.get(input).cloned(),
+        }
+    }
+
+    // a physical key maps to one Keys at a time, binding it again moves it.
+    pub fn bind(&mut self, keycode: Keycode, key: Keys) {
+        self.bind_input(PhysicalInput::Key(keycode), key);
+    }
+
+    pub fn bind_input(&mut self, input: PhysicalInput, key: Keys) {
+        self.inputs.insert(input, key);
+    }
+
+    pub fn unbind(&mut self, keycode: &Keycode) {
+        self.unbind_input(&PhysicalInput::Key(*keycode));
+    }
+
+    pub fn unbind_input(&mut self, input: &PhysicalInput) {
+        self.inputs.remove(input);
+    }
+
+    pub fn unbind_all(&mut self, key: Keys) {
+        self.inputs.retain(|_, bound| *bound != key);
+    }
+
+    pub fn keycodes(&self, key: Keys) -> Vec<Keycode> {
+        let mut keycodes = self.inputs(key)
+            .into_iter()
+            .filter_map(|input| match input {
+                PhysicalInput::Key(keycode) => Some(keycode),
+                _ => None,
+            })
+            .collect::<Vec<Keycode>>();
+        keycodes.sort_by_key(|keycode| *keycode as i32);
+        keycodes
+    }
+
+    pub fn inputs(&self, key: Keys) -> Vec<PhysicalInput> {
+        self.inputs
+            .iter()
+            .filter(|(_, bound)| **bound == key)
+            .map(|(input, _)| *input)
+            .collect()
+    }
+
+    // maps physical inputs to Keys, dropping unbound and duplicate keys.
+    pub(crate) fn map_keys<'k>(&self, inputs: impl Iterator<Item = &'k PhysicalInput>) -> Vec<Keys> {
+        inputs
+            .filter_map(|input| self.get_input(input))
+            .collect::<BTreeSet<Keys>>()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut bindings = KeyBindings::empty();
+        parse_settings(text, |name, inputs| {
+            if name == "Deadzone" {
+                bindings.axis_deadzone = inputs.parse().map_err(|_| "invalid deadzone")?;
+                return Ok(());
+            }
+
+            let key = Keys::from_name(name).ok_or("unknown key")?;
+            for input_name in split_names(inputs)? {
+                let input = PhysicalInput::from_name(&input_name).ok_or("unknown keycode")?;
+                bindings.bind_input(input, key);
+            }
+            Ok(())
+        })?;
+
+        Ok(bindings)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // falls back to the default GameShell layout if the file is missing or broken.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        Self::load(path).unwrap_or_default()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = Keys::ALL
+            .iter()
+            .map(|key| {
+                let mut names = self.inputs(*key)
+                    .iter()
+                    .filter_map(PhysicalInput::name)
+                    .map(|name| quote_name(&name))
+                    .collect::<Vec<String>>();
+                names.sort();
+                format!("{} = {}\n", key.name(), names.join(", "))
+            })
+            .collect::<String>();
+        text.push_str(&format!("Deadzone = {}\n", self.axis_deadzone));
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_quoted_names() {
+        assert_eq!(split_names("J, Z ,pad:a,, joy:0").unwrap(), vec!["J", "Z", "pad:a", "joy:0"]);
+        assert_eq!(split_names(r#"",", "Keypad ,", "\"", "\\""#).unwrap(), vec![",", "Keypad ,", "\"", "\\"]);
+        assert!(split_names("").unwrap().is_empty());
+        assert!(split_names(r#""J, K"#).is_err());
+        assert!(split_names(r#"",", J"#).is_ok());
+        assert!(split_names(r#"",". J"#).is_err());
+    }
+
+    #[test]
+    fn quotes_names_split_names_would_break() {
+        for name in [",", "Keypad ,", "\"", "\"\\", "J", "\\"] {
+            assert_eq!(split_names(&quote_name(name)).unwrap(), vec![name]);
+        }
+        assert_eq!(quote_name("Return"), "Return");
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let mut bindings = KeyBindings::empty();
+        bindings.bind(Keycode::Comma, Keys::A);
+        bindings.bind(Keycode::J, Keys::A);
+        bindings.bind(Keycode::KpComma, Keys::B);
+        bindings.bind(Keycode::Quotedbl, Keys::X);
+        bindings.bind_input(PhysicalInput::JoyButton(7), Keys::Start);
+        bindings.axis_deadzone = 1234;
+
+        let parsed = KeyBindings::parse(&bindings.to_text()).unwrap();
+        assert_eq!(parsed.inputs, bindings.inputs);
+        assert_eq!(parsed.axis_deadzone, 1234);
+    }
+}
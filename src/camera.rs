@@ -0,0 +1,174 @@
+use specs::{Entity, World, WorldExt};
+use crate::components::{Position, Transform};
+use crate::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::ImageResource;
+
+struct Shake {
+    start_ticks: u32,
+    duration_ms: u32,
+    magnitude: f32,
+}
+
+// the visible part of the world. RenderingHelper draws entities relative to
+// it; anything drawn directly on RenderContext stays in screen space.
+pub struct Camera {
+    // top-left corner of the view, in world pixels.
+    pub x: f32,
+    pub y: f32,
+    pub target: Option<Entity>,
+    // fraction of the distance to the target covered each frame, 1.0 snaps to it.
+    pub follow_speed: f32,
+    // world area the view may not leave: x, y, width, height.
+    pub bounds: Option<(f32, f32, f32, f32)>,
+    viewport_width: u32,
+    viewport_height: u32,
+    shake: Option<Shake>,
+    shake_offset: (i32, i32),
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            x: 0.0,
+            y: 0.0,
+            target: None,
+            follow_speed: 1.0,
+            bounds: None,
+            viewport_width: SCREEN_WIDTH,
+            viewport_height: SCREEN_HEIGHT,
+            shake: None,
+            shake_offset: (0, 0),
+        }
+    }
+}
+
+impl Camera {
+    pub fn follow(&mut self, target: Entity, follow_speed: f32) {
+        self.target = Some(target);
+        self.follow_speed = follow_speed.clamp(0.0, 1.0);
+    }
+
+    pub fn stop_following(&mut self) {
+        self.target = None;
+    }
+
+    pub fn look_at(&mut self, x: f32, y: f32) {
+        self.x = x - self.viewport_width as f32 / 2.0;
+        self.y = y - self.viewport_height as f32 / 2.0;
+        self.clamp_to_bounds();
+    }
+
+    pub fn set_bounds(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.bounds = Some((x, y, width, height));
+        self.clamp_to_bounds();
+    }
+
+    pub fn clear_bounds(&mut self) {
+        self.bounds = None;
+    }
+
+    pub fn shake(&mut self, magnitude: f32, duration_ms: u32, ticks: u32) {
+        self.shake = Some(Shake { start_ticks: ticks, duration_ms, magnitude });
+    }
+
+    pub fn is_shaking(&self) -> bool {
+        self.shake.is_some()
+    }
+
+    pub fn viewport_size(&self) -> (u32, u32) {
+        (self.viewport_width, self.viewport_height)
+    }
+
+    pub(crate) fn set_viewport_size(&mut self, width: u32, height: u32) {
+        self.viewport_width = width;
+        self.viewport_height = height;
+    }
+
+    // amount to subtract from world coordinates to get screen coordinates.
+    pub fn offset(&self) -> (i32, i32) {
+        (
+            self.x.round() as i32 + self.shake_offset.0,
+            self.y.round() as i32 + self.shake_offset.1,
+        )
+    }
+
+    pub fn world_to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        let (offset_x, offset_y) = self.offset();
+        (x - offset_x, y - offset_y)
+    }
+
+    pub fn screen_to_world(&self, x: i32, y: i32) -> (i32, i32) {
+        let (offset_x, offset_y) = self.offset();
+        (x + offset_x, y + offset_y)
+    }
+
+    fn clamp_to_bounds(&mut self) {
+        if let Some((x, y, width, height)) = self.bounds {
+            let max_x = x + width - self.viewport_width as f32;
+            let max_y = y + height - self.viewport_height as f32;
+            // a world smaller than the screen is centered instead.
+            self.x = if max_x < x { x + (width - self.viewport_width as f32) / 2.0 } else { self.x.clamp(x, max_x) };
+            self.y = if max_y < y { y + (height - self.viewport_height as f32) / 2.0 } else { self.y.clamp(y, max_y) };
+        }
+    }
+
+    fn update_shake(&mut self, ticks: u32) {
+        let (start_ticks, duration_ms, magnitude) = match &self.shake {
+            Some(shake) => (shake.start_ticks, shake.duration_ms, shake.magnitude),
+            None => return,
+        };
+
+        let elapsed = ticks.saturating_sub(start_ticks);
+        if elapsed >= duration_ms {
+            self.shake = None;
+            self.shake_offset = (0, 0);
+            return;
+        }
+
+        // derived from the ticks rather than a random source, so replays shake the same.
+        let strength = magnitude * (1.0 - elapsed as f32 / duration_ms as f32);
+        let noise = |seed: u32| {
+            let mut n = seed.wrapping_mul(0x9E37_79B9) ^ 0x85EB_CA6B;
+            n ^= n >> 15;
+            n = n.wrapping_mul(0x2C1B_3C6D);
+            n ^= n >> 12;
+            (n % 2001) as f32 / 1000.0 - 1.0
+        };
+        self.shake_offset = (
+            (noise(ticks) * strength).round() as i32,
+            (noise(ticks ^ 0xFFFF) * strength).round() as i32,
+        );
+    }
+}
+
+// world pixel position of an entity, from its Transform or else its Position.
+pub(crate) fn entity_position(world: &World, entity: Entity, tile_position: impl Fn(&Position) -> (i32, i32)) -> Option<(f32, f32)> {
+    let transforms = world.read_storage::<Transform>();
+    let positions = world.read_storage::<Position>();
+    match (transforms.get(entity), positions.get(entity)) {
+        (Some(transform), _) => Some((transform.x, transform.y)),
+        (None, Some(position)) => {
+            let (x, y) = tile_position(position);
+            Some((x as f32, y as f32))
+        },
+        (None, None) => None,
+    }
+}
+
+pub(crate) fn update_camera<IR: ImageResource>(world: &World, resource: &IR, ticks: u32) {
+    let mut camera = world.write_resource::<Camera>();
+
+    if let Some(target) = camera.target {
+        let center = entity_position(world, target, |position| resource.get_tile_position(position));
+
+        if let Some((x, y)) = center {
+            let target_x = x - camera.viewport_width as f32 / 2.0;
+            let target_y = y - camera.viewport_height as f32 / 2.0;
+            camera.x += (target_x - camera.x) * camera.follow_speed;
+            camera.y += (target_y - camera.y) * camera.follow_speed;
+        }
+    }
+
+    camera.clamp_to_bounds();
+    camera.update_shake(ticks);
+}
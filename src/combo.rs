@@ -0,0 +1,269 @@
+use std::collections::{HashSet, VecDeque};
+use crate::input::Keys;
+
+const DEFAULT_BUFFER_CAPACITY: usize = 64;
+const DEFAULT_STEP_WINDOW_MS: u32 = 250;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyTransition {
+    pub key: Keys,
+    // true when the key went down, false when it went up.
+    pub pressed: bool,
+    pub ticks: u32,
+}
+
+// the most recent key transitions, oldest first.
+pub struct InputBuffer {
+    transitions: VecDeque<KeyTransition>,
+    capacity: usize,
+}
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        InputBuffer::with_capacity(DEFAULT_BUFFER_CAPACITY)
+    }
+}
+
+impl InputBuffer {
+    pub fn with_capacity(capacity: usize) -> Self {
+        InputBuffer {
+            transitions: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, transition: KeyTransition) {
+        if self.transitions.len() == self.capacity {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(transition);
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &KeyTransition> {
+        self.transitions.iter()
+    }
+
+    // transitions that happened at or after the given ticks.
+    pub fn since(&self, ticks: u32) -> impl Iterator<Item = &KeyTransition> {
+        self.transitions.iter().filter(move |transition| transition.ticks >= ticks)
+    }
+
+    pub fn last(&self) -> Option<&KeyTransition> {
+        self.transitions.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transitions.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.transitions.clear();
+    }
+}
+
+// a sequence of steps, each a set of keys that must be held together. a step
+// completes when one of its keys is pressed while the others are down, so
+// Down, Down+Right, Right+A is a quarter-circle forward punch.
+#[derive(Clone, Debug)]
+pub struct Combo {
+    pub name: String,
+    pub steps: Vec<Vec<Keys>>,
+    // max time between two steps.
+    pub step_window_ms: u32,
+    // max time from the first to the last step, 0 for no limit.
+    pub total_window_ms: u32,
+}
+
+impl Combo {
+    pub fn new(name: &str) -> Self {
+        Combo {
+            name: name.to_string(),
+            steps: Vec::new(),
+            step_window_ms: DEFAULT_STEP_WINDOW_MS,
+            total_window_ms: 0,
+        }
+    }
+
+    pub fn then(mut self, keys: &[Keys]) -> Self {
+        self.steps.push(keys.to_vec());
+        self
+    }
+
+    pub fn step_window(mut self, ms: u32) -> Self {
+        self.step_window_ms = ms;
+        self
+    }
+
+    pub fn total_window(mut self, ms: u32) -> Self {
+        self.total_window_ms = ms;
+        self
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ComboMatch {
+    pub name: String,
+    pub ticks: u32,
+}
+
+// matched combos, consumed by the game like InputQueue.
+#[derive(Default)]
+pub struct ComboQueue {
+    pub combos: Vec<ComboMatch>,
+}
+
+// one partial match of a combo.
+#[derive(Clone, Copy)]
+struct ComboProgress {
+    // steps completed so far.
+    step: usize,
+    start_ticks: u32,
+    last_ticks: u32,
+}
+
+// combos are matched against every suffix of the input that could still
+// complete them, so Up, Up, Down still matches after an extra Up.
+#[derive(Default)]
+pub struct ComboMatcher {
+    combos: Vec<(Combo, Vec<ComboProgress>)>,
+    keys_held: HashSet<Keys>,
+}
+
+impl ComboMatcher {
+    pub fn register(&mut self, combo: Combo) {
+        if !combo.steps.is_empty() {
+            self.combos.push((combo, Vec::new()));
+        }
+    }
+
+    pub fn unregister(&mut self, name: &str) {
+        self.combos.retain(|(combo, _)| combo.name != name);
+    }
+
+    pub fn clear(&mut self) {
+        self.combos.clear();
+    }
+
+    pub(crate) fn feed(&mut self, transition: &KeyTransition, queue: &mut ComboQueue) {
+        if !transition.pressed {
+            self.keys_held.remove(&transition.key);
+            return;
+        }
+        self.keys_held.insert(transition.key);
+
+        let ticks = transition.ticks;
+        let keys_held = &self.keys_held;
+        let completes = |step: &Vec<Keys>| {
+            step.contains(&transition.key) && step.iter().all(|key| keys_held.contains(key))
+        };
+
+        for (combo, partials) in self.combos.iter_mut() {
+            let in_time = |progress: &ComboProgress| {
+                ticks - progress.last_ticks <= combo.step_window_ms &&
+                    (combo.total_window_ms == 0 || ticks - progress.start_ticks <= combo.total_window_ms)
+            };
+
+            let mut advanced: Vec<ComboProgress> = partials
+                .iter()
+                .filter(|progress| in_time(progress) && completes(&combo.steps[progress.step]))
+                .map(|progress| ComboProgress { step: progress.step + 1, last_ticks: ticks, ..*progress })
+                .collect();
+            if completes(&combo.steps[0]) {
+                advanced.push(ComboProgress { step: 1, start_ticks: ticks, last_ticks: ticks });
+            }
+            // of two matches at the same step, the later start has more of
+            // the total window left.
+            advanced.sort_by_key(|progress| (progress.step, u32::MAX - progress.start_ticks));
+            advanced.dedup_by_key(|progress| progress.step);
+
+            if advanced.iter().any(|progress| progress.step == combo.steps.len()) {
+                queue.combos.push(ComboMatch { name: combo.name.clone(), ticks });
+                advanced.clear();
+            }
+            *partials = advanced;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(matcher: &mut ComboMatcher, inputs: &[(Keys, bool, u32)]) -> Vec<String> {
+        let mut queue = ComboQueue::default();
+        for (key, pressed, ticks) in inputs {
+            matcher.feed(&KeyTransition { key: *key, pressed: *pressed, ticks: *ticks }, &mut queue);
+        }
+        queue.combos.into_iter().map(|combo| combo.name).collect()
+    }
+
+    fn taps(keys: &[Keys], interval: u32) -> Vec<(Keys, bool, u32)> {
+        keys.iter()
+            .enumerate()
+            .flat_map(|(i, key)| {
+                let ticks = i as u32 * interval;
+                [(*key, true, ticks), (*key, false, ticks + 10)]
+            })
+            .collect()
+    }
+
+    fn matcher(combo: Combo) -> ComboMatcher {
+        let mut matcher = ComboMatcher::default();
+        matcher.register(combo);
+        matcher
+    }
+
+    #[test]
+    fn matches_a_sequence() {
+        let mut matcher = matcher(Combo::new("hadoken").then(&[Keys::Down]).then(&[Keys::Right]).then(&[Keys::A]));
+        assert_eq!(feed(&mut matcher, &taps(&[Keys::Down, Keys::Right, Keys::A], 100)), vec!["hadoken"]);
+        assert!(feed(&mut matcher, &taps(&[Keys::Down, Keys::Left, Keys::A], 100)).is_empty());
+    }
+
+    #[test]
+    fn matches_keys_held_together() {
+        let mut matcher = matcher(Combo::new("dash").then(&[Keys::Down]).then(&[Keys::Down, Keys::Right]));
+        let inputs = [
+            (Keys::Down, true, 0),
+            (Keys::Down, false, 50),
+            (Keys::Down, true, 100),
+            (Keys::Right, true, 120),
+        ];
+        assert_eq!(feed(&mut matcher, &inputs), vec!["dash"]);
+
+        // Right without Down held is not the second step.
+        let mut matcher = self::matcher(Combo::new("dash").then(&[Keys::Down]).then(&[Keys::Down, Keys::Right]));
+        let inputs = [(Keys::Down, true, 0), (Keys::Down, false, 50), (Keys::Right, true, 100)];
+        assert!(feed(&mut matcher, &inputs).is_empty());
+    }
+
+    #[test]
+    fn steps_and_sequences_time_out() {
+        let combo = Combo::new("jab").then(&[Keys::A]).then(&[Keys::B]).step_window(200);
+        assert!(feed(&mut matcher(combo.clone()), &taps(&[Keys::A, Keys::B], 201)).is_empty());
+        assert_eq!(feed(&mut matcher(combo), &taps(&[Keys::A, Keys::B], 200)), vec!["jab"]);
+
+        let combo = Combo::new("triple").then(&[Keys::A]).then(&[Keys::A]).then(&[Keys::A]).total_window(300);
+        assert!(feed(&mut matcher(combo.clone()), &taps(&[Keys::A, Keys::A, Keys::A], 160)).is_empty());
+        assert_eq!(feed(&mut matcher(combo), &taps(&[Keys::A, Keys::A, Keys::A], 150)), vec!["triple"]);
+    }
+
+    #[test]
+    fn matches_after_an_extra_repeated_key() {
+        let mut matcher = matcher(Combo::new("code").then(&[Keys::Up]).then(&[Keys::Up]).then(&[Keys::Down]));
+        assert_eq!(feed(&mut matcher, &taps(&[Keys::Up, Keys::Up, Keys::Up, Keys::Down], 100)), vec!["code"]);
+        assert_eq!(feed(&mut matcher, &taps(&[Keys::Up, Keys::Up, Keys::Down], 100)), vec!["code"]);
+    }
+
+    #[test]
+    fn restarts_from_a_later_press_after_a_total_timeout() {
+        let combo = Combo::new("triple").then(&[Keys::A]).then(&[Keys::A]).then(&[Keys::A]).total_window(250);
+        // the press at 0 is too early for the one at 300, but 200, 300, 350 fit.
+        let inputs = [(Keys::A, true, 0), (Keys::A, true, 200), (Keys::A, true, 300), (Keys::A, true, 350)];
+        assert_eq!(feed(&mut matcher(combo), &inputs), vec!["triple"]);
+    }
+}
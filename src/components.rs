@@ -9,30 +9,99 @@ pub struct Position {
     pub z: u8,
 }
 
+// pixel-precise placement, drawn instead of Position when an entity has both.
+#[derive(Debug, Component, Clone, Copy)]
+#[storage(VecStorage)]
+pub struct Transform {
+    pub x: f32,
+    pub y: f32,
+    // draw layer, same as Position::z.
+    pub z: u8,
+    pub scale_x: f32,
+    pub scale_y: f32,
+    // degrees clockwise, around the center of the sprite.
+    pub rotation: f64,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Transform {
+    pub fn new(x: f32, y: f32, z: u8) -> Self {
+        Transform {
+            x,
+            y,
+            z,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotation: 0.0,
+            flip_horizontal: false,
+            flip_vertical: false,
+        }
+    }
+
+    pub fn with_scale(mut self, scale_x: f32, scale_y: f32) -> Self {
+        self.scale_x = scale_x;
+        self.scale_y = scale_y;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f64) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_flip(mut self, horizontal: bool, vertical: bool) -> Self {
+        self.flip_horizontal = horizontal;
+        self.flip_vertical = vertical;
+        self
+    }
+}
+
 pub enum RenderableKind {
     Static,
     Animated,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Sprite<Id> {
+    pub id: Id,
+    // frame of the image's atlas, None draws the whole image.
+    pub frame: Option<usize>,
+}
+
 #[derive(Component)]
 #[storage(VecStorage)]
 pub struct Renderable<IR: ImageResource + 'static>
     where <IR as ImageResource>::TextureId: Send + Sync {
-    ids: Vec<IR::TextureId>,
+    sprites: Vec<Sprite<IR::TextureId>>,
 }
 
 impl<IR: ImageResource> Renderable<IR>
     where <IR as ImageResource>::TextureId: Send + Sync {
     pub fn new_static(id: IR::TextureId) -> Self {
-        Self { ids: vec![id] }
+        Self { sprites: vec![Sprite { id, frame: None }] }
     }
 
     pub fn new_animated(ids: Vec<IR::TextureId>) -> Self {
-        Self { ids }
+        Self { sprites: ids.into_iter().map(|id| Sprite { id, frame: None }).collect() }
+    }
+
+    pub fn new_frame(id: IR::TextureId, frame: usize) -> Self {
+        Self { sprites: vec![Sprite { id, frame: Some(frame) }] }
+    }
+
+    // animates through frames of one sprite sheet.
+    pub fn new_frames(id: IR::TextureId, frames: Vec<usize>) -> Self {
+        Self {
+            sprites: frames
+                .into_iter()
+                .map(|frame| Sprite { id: id.clone(), frame: Some(frame) })
+                .collect(),
+        }
     }
 
     pub fn kind(&self) -> RenderableKind {
-        match self.ids.len() {
+        match self.sprites.len() {
             0 => panic!("invalid renderable"),
             1 => RenderableKind::Static,
             _ => RenderableKind::Animated,
@@ -40,12 +109,136 @@ impl<IR: ImageResource> Renderable<IR>
     }
 
     pub fn texture_id(&self, id_index: usize) -> IR::TextureId {
-        self.ids[id_index % self.ids.len()].clone()
+        self.sprite(id_index).id
+    }
+
+    pub fn sprite(&self, id_index: usize) -> Sprite<IR::TextureId> {
+        self.sprites[id_index % self.sprites.len()].clone()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoopMode {
+    Once,
+    Loop,
+    PingPong,
+}
+
+// picks which of a Renderable's frames to draw. frame_durations has one entry
+// per frame, in milliseconds.
+#[derive(Debug, Component, Clone)]
+#[storage(VecStorage)]
+pub struct Animation {
+    pub frame_durations: Vec<u32>,
+    pub mode: LoopMode,
+    pub speed: f32,
+    start_ticks: u32,
+    playing: bool,
+    // elapsed time at the moment the animation was stopped.
+    stopped_ms: u32,
+}
+
+impl Animation {
+    pub fn new(frame_durations: Vec<u32>, mode: LoopMode) -> Self {
+        Animation {
+            frame_durations,
+            mode,
+            speed: 1.0,
+            start_ticks: 0,
+            playing: true,
+            stopped_ms: 0,
+        }
+    }
+
+    pub fn uniform(frames: usize, duration_ms: u32, mode: LoopMode) -> Self {
+        Self::new(vec![duration_ms; frames], mode)
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn starting_at(mut self, ticks: u32) -> Self {
+        self.start_ticks = ticks;
+        self
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    // resumes from where stop left off.
+    pub fn play(&mut self, ticks: u32) {
+        if !self.playing {
+            self.start_ticks = ticks.saturating_sub((self.stopped_ms as f32 / self.speed.max(f32::EPSILON)) as u32);
+            self.playing = true;
+        }
+    }
+
+    pub fn stop(&mut self, ticks: u32) {
+        if self.playing {
+            self.stopped_ms = self.elapsed_ms(ticks);
+            self.playing = false;
+        }
+    }
+
+    pub fn restart(&mut self, ticks: u32) {
+        self.start_ticks = ticks;
+        self.stopped_ms = 0;
+        self.playing = true;
+    }
+
+    pub fn is_finished(&self, ticks: u32) -> bool {
+        self.mode == LoopMode::Once && self.elapsed_ms(ticks) >= self.frame_durations.iter().sum()
+    }
+
+    fn elapsed_ms(&self, ticks: u32) -> u32 {
+        if self.playing {
+            (ticks.saturating_sub(self.start_ticks) as f32 * self.speed) as u32
+        } else {
+            self.stopped_ms
+        }
+    }
+
+    pub fn frame_index(&self, ticks: u32) -> usize {
+        let frames = self.frame_durations.len();
+        if frames < 2 {
+            return 0;
+        }
+
+        // ping-pong plays 0..n then n-1..1 again, without repeating the ends.
+        let sequence = match self.mode {
+            LoopMode::PingPong => (0..frames).chain((1..frames - 1).rev()).collect::<Vec<usize>>(),
+            _ => (0..frames).collect::<Vec<usize>>(),
+        };
+        let total = sequence.iter().map(|index| self.frame_durations[*index]).sum::<u32>();
+        if total == 0 {
+            return 0;
+        }
+
+        let elapsed = self.elapsed_ms(ticks);
+        let mut time = match self.mode {
+            LoopMode::Once if elapsed >= total => return frames - 1,
+            LoopMode::Once => elapsed,
+            _ => elapsed % total,
+        };
+
+        for index in sequence {
+            let duration = self.frame_durations[index];
+            if time < duration {
+                return index;
+            }
+            time -= duration;
+        }
+        0
     }
 }
 
 pub(crate) fn register_components<IR: ImageResource + 'static>(world: &mut World)
     where <IR as ImageResource>::TextureId: Send + Sync {
     world.register::<Position>();
+    world.register::<Transform>();
     world.register::<Renderable<IR>>();
+    world.register::<Animation>();
 }
\ No newline at end of file
@@ -0,0 +1,164 @@
+use crate::constants::{
+    DEFAULT_AUDIO_CHANNELS, DEFAULT_AUDIO_CHUNK_SIZE, DEFAULT_AUDIO_FREQUENCY, DEFAULT_FPS,
+    DEFAULT_MIXING_CHANNELS, DEFAULT_TEXT_CACHE_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH, WINDOW_TITLE,
+};
+use std::path::{Path, PathBuf};
+use crate::texture::RenderMode;
+
+#[derive(Clone, Debug)]
+pub struct GameConfig {
+    pub(crate) title: String,
+    pub(crate) screen_width: u32,
+    pub(crate) screen_height: u32,
+    pub(crate) window_width: u32,
+    pub(crate) window_height: u32,
+    pub(crate) fps: u32,
+    pub(crate) render_mode: RenderMode,
+    pub(crate) text_cache_size: usize,
+    pub(crate) audio_frequency: i32,
+    pub(crate) audio_channels: i32,
+    pub(crate) audio_chunk_size: i32,
+    pub(crate) mixing_channels: i32,
+    pub(crate) volume_settings: Option<PathBuf>,
+    pub(crate) record_input: Option<PathBuf>,
+    pub(crate) replay_input: Option<PathBuf>,
+    pub(crate) exit_after_replay: bool,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            title: WINDOW_TITLE.to_string(),
+            screen_width: SCREEN_WIDTH,
+            screen_height: SCREEN_HEIGHT,
+            window_width: SCREEN_WIDTH,
+            window_height: SCREEN_HEIGHT,
+            fps: DEFAULT_FPS,
+            render_mode: RenderMode::Window,
+            text_cache_size: DEFAULT_TEXT_CACHE_SIZE,
+            audio_frequency: DEFAULT_AUDIO_FREQUENCY,
+            audio_channels: DEFAULT_AUDIO_CHANNELS,
+            audio_chunk_size: DEFAULT_AUDIO_CHUNK_SIZE,
+            mixing_channels: DEFAULT_MIXING_CHANNELS,
+            volume_settings: None,
+            record_input: None,
+            replay_input: None,
+            exit_after_replay: false,
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    // logical resolution the game draws in. also resizes the window
+    // unless window_size is set afterwards.
+    pub fn screen_size(mut self, width: u32, height: u32) -> Self {
+        self.screen_width = width;
+        self.screen_height = height;
+        self.window_width = width;
+        self.window_height = height;
+        self
+    }
+
+    // physical window size. the logical screen is scaled to fit it.
+    pub fn window_size(mut self, width: u32, height: u32) -> Self {
+        self.window_width = width;
+        self.window_height = height;
+        self
+    }
+
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.fps = fps.max(1);
+        self
+    }
+
+    pub fn render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    pub fn headless(self) -> Self {
+        self.render_mode(RenderMode::Headless)
+    }
+
+    // number of strings RenderContext::draw_text_cached keeps rendered.
+    pub fn text_cache_size(mut self, size: usize) -> Self {
+        self.text_cache_size = size;
+        self
+    }
+
+    pub fn audio_frequency(mut self, frequency: i32) -> Self {
+        self.audio_frequency = frequency;
+        self
+    }
+
+    // output channels, 1 for mono and 2 for stereo.
+    pub fn audio_channels(mut self, channels: i32) -> Self {
+        self.audio_channels = channels;
+        self
+    }
+
+    pub fn audio_chunk_size(mut self, chunk_size: i32) -> Self {
+        self.audio_chunk_size = chunk_size;
+        self
+    }
+
+    // number of sounds the mixer can play at the same time.
+    pub fn mixing_channels(mut self, channels: i32) -> Self {
+        self.mixing_channels = channels;
+        self
+    }
+
+    // file the volume settings are restored from at startup and saved to
+    // when the game ends.
+    pub fn volume_settings<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.volume_settings = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // writes every frame's input to the file, see InputRecorder.
+    pub fn record_input<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.record_input = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // feeds the game from a recording instead of the keyboard. live input
+    // takes over when the recording ends, unless exit_after_replay is set.
+    pub fn replay_input<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.replay_input = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn exit_after_replay(mut self, exit: bool) -> Self {
+        self.exit_after_replay = exit;
+        self
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn get_screen_size(&self) -> (u32, u32) {
+        (self.screen_width, self.screen_height)
+    }
+
+    pub fn get_window_size(&self) -> (u32, u32) {
+        (self.window_width, self.window_height)
+    }
+
+    pub fn get_fps(&self) -> u32 {
+        self.fps
+    }
+
+    pub fn get_render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+}
@@ -1,8 +1,15 @@
 // use sdl2::pixels::Color;
 
+// defaults for GameConfig
 pub const WINDOW_TITLE: &str = "demo";
 pub const SCREEN_WIDTH: u32 = 320;
 pub const SCREEN_HEIGHT: u32 = 240;
+pub const DEFAULT_FPS: u32 = 60;
+pub const DEFAULT_TEXT_CACHE_SIZE: usize = 64;
+pub const DEFAULT_AUDIO_FREQUENCY: i32 = 44_100;
+pub const DEFAULT_AUDIO_CHANNELS: i32 = 2; // Stereo
+pub const DEFAULT_AUDIO_CHUNK_SIZE: i32 = 1_024;
+pub const DEFAULT_MIXING_CHANNELS: i32 = 4;
 // pub const TILE_WIDTH: i32 = 24;
 // pub const MAP_OFFSET_X: i32 = 12;
 // pub const MAP_OFFSET_Y: i32 = 12;
@@ -10,4 +17,4 @@ pub const SCREEN_HEIGHT: u32 = 240;
 // pub const MAP_HEIGHT: u8 = 9;
 // pub const TEXT_OFFSET_X: i32 = MAP_OFFSET_X + MAP_WIDTH as i32 * TILE_WIDTH + 12;
 // pub const TEXT_FOOTER_Y: i32 = SCREEN_HEIGHT as i32 - 24;
-// pub const BG_COLOR: Color = Color::WHITE;
\ No newline at end of file
+// pub const BG_COLOR: Color = Color::WHITE;
@@ -0,0 +1,82 @@
+use std::fmt;
+use sdl2::render::{TargetRenderError, TextureValueError};
+use sdl2::video::WindowBuildError;
+use sdl2::IntegerOrSdlError;
+
+#[derive(Debug)]
+pub enum Error {
+    // SDL itself or one of its subsystems failed to initialize.
+    Sdl(String),
+    Window(String),
+    Render(String),
+    Image(String),
+    Audio(String),
+    Io(String),
+    Config(String),
+    Replay(String),
+    Map(String),
+    Font(String),
+    TextureNotFound,
+    FrameNotFound,
+    SoundNotFound,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sdl(message) => write!(f, "failed to initialize SDL: {}", message),
+            Error::Window(message) => write!(f, "failed to create window: {}", message),
+            Error::Render(message) => write!(f, "failed to render: {}", message),
+            Error::Image(message) => write!(f, "failed to load image: {}", message),
+            Error::Audio(message) => write!(f, "audio error: {}", message),
+            Error::Io(message) => write!(f, "io error: {}", message),
+            Error::Config(message) => write!(f, "invalid config: {}", message),
+            Error::Replay(message) => write!(f, "invalid input recording: {}", message),
+            Error::Map(message) => write!(f, "invalid tile map: {}", message),
+            Error::Font(message) => write!(f, "invalid font: {}", message),
+            Error::TextureNotFound => write!(f, "texture not found"),
+            Error::FrameNotFound => write!(f, "atlas frame not found"),
+            Error::SoundNotFound => write!(f, "sound not found"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error.to_string())
+    }
+}
+
+impl From<WindowBuildError> for Error {
+    fn from(error: WindowBuildError) -> Self {
+        Error::Window(error.to_string())
+    }
+}
+
+impl From<IntegerOrSdlError> for Error {
+    fn from(error: IntegerOrSdlError) -> Self {
+        Error::Render(error.to_string())
+    }
+}
+
+impl From<TextureValueError> for Error {
+    fn from(error: TextureValueError) -> Self {
+        Error::Render(error.to_string())
+    }
+}
+
+impl From<TargetRenderError> for Error {
+    fn from(error: TargetRenderError) -> Self {
+        Error::Render(error.to_string())
+    }
+}
+
+impl From<imagesize::ImageError> for Error {
+    fn from(error: imagesize::ImageError) -> Self {
+        Error::Image(error.to_string())
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::HashMap;
+use sdl2::pixels::Color;
+use crate::error::{Error, Result};
+
+// one character of a font image, in pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyph {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    // distance from the pen position to the top-left corner of the glyph.
+    pub offset_x: i32,
+    pub offset_y: i32,
+    // how far the pen moves after drawing the glyph.
+    pub advance: i32,
+    pub page: u32,
+}
+
+pub struct Font {
+    pub line_height: u32,
+    // distance from the top of a line to the baseline.
+    pub base: u32,
+    // drawn in place of characters the font has no glyph for.
+    pub replacement: Option<char>,
+    glyphs: HashMap<char, Glyph>,
+    kernings: HashMap<(char, char), i32>,
+}
+
+// code points of a grid font mapped to cells, for fonts beyond the single
+// ascii row: accented letters, kana, kanji. cells are numbered row by row
+// and each page is a separate image.
+#[derive(Clone, Debug)]
+pub struct GlyphMap {
+    pub cell_width: u32,
+    pub cell_height: u32,
+    // cells per row of every page.
+    pub columns: u32,
+    pub replacement: Option<char>,
+    cells: HashMap<char, (u32, u32)>,
+}
+
+impl GlyphMap {
+    pub fn new(cell_width: u32, cell_height: u32, columns: u32) -> Self {
+        GlyphMap {
+            cell_width,
+            cell_height,
+            columns: columns.max(1),
+            replacement: Some('?'),
+            cells: HashMap::new(),
+        }
+    }
+
+    // maps the code points first..=last to consecutive cells of a page.
+    pub fn range(mut self, first: char, last: char, page: u32, first_cell: u32) -> Self {
+        for (offset, char) in (first..=last).enumerate() {
+            self.cells.insert(char, (page, first_cell + offset as u32));
+        }
+        self
+    }
+
+    // maps each character of chars to consecutive cells of a page, for
+    // sparse sets such as a list of the kanji a game uses.
+    pub fn chars(mut self, chars: &str, page: u32, first_cell: u32) -> Self {
+        for (offset, char) in chars.chars().enumerate() {
+            self.cells.insert(char, (page, first_cell + offset as u32));
+        }
+        self
+    }
+
+    pub fn replacement(mut self, replacement: Option<char>) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    pub fn cell(&self, char: char) -> Option<(u32, u32)> {
+        self.cells.get(&char).cloned()
+    }
+}
+
+fn font_error(message: impl Into<String>) -> Error {
+    Error::Font(message.into())
+}
+
+// splits `key=value key="quoted value"` pairs of a BMFont text line.
+fn fnt_pairs(line: &str) -> HashMap<&str, &str> {
+    let mut pairs = HashMap::new();
+    let mut rest = line;
+
+    while let Some(equals) = rest.find('=') {
+        let key = rest[..equals].trim();
+        let value_start = &rest[equals + 1..];
+        let (value, next) = match value_start.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], &quoted[(end + 1).min(quoted.len())..])
+            },
+            None => {
+                let end = value_start.find(char::is_whitespace).unwrap_or(value_start.len());
+                (&value_start[..end], &value_start[end..])
+            },
+        };
+        pairs.insert(key, value);
+        rest = next;
+    }
+
+    pairs
+}
+
+fn fnt_value<T: std::str::FromStr + Default>(pairs: &HashMap<&str, &str>, key: &str) -> Result<T> {
+    match pairs.get(key) {
+        Some(value) => value.parse().map_err(|_| font_error(format!("invalid `{}` value `{}`", key, value))),
+        None => Ok(T::default()),
+    }
+}
+
+fn fnt_char(pairs: &HashMap<&str, &str>, key: &str) -> Result<char> {
+    let code: u32 = fnt_value(pairs, key)?;
+    char::from_u32(code).ok_or_else(|| font_error(format!("invalid character code {}", code)))
+}
+
+impl Font {
+    // the layout of the original fonts: square glyphs in a single row,
+    // starting from the space character.
+    pub fn monospace(font_height: u32, image_width: u32) -> Self {
+        let columns = image_width / font_height.max(1);
+        let glyphs = (0..columns)
+            .filter_map(|index| char::from_u32(0x20 + index).map(|char| (char, index)))
+            .map(|(char, index)| (char, Glyph {
+                x: (index * font_height) as i32,
+                y: 0,
+                width: font_height,
+                height: font_height,
+                offset_x: 0,
+                offset_y: 0,
+                advance: font_height as i32,
+                page: 0,
+            }))
+            .collect();
+
+        Font {
+            line_height: font_height,
+            base: font_height,
+            replacement: Some('?'),
+            glyphs,
+            kernings: HashMap::new(),
+        }
+    }
+
+    pub fn from_glyph_map(map: &GlyphMap) -> Self {
+        let glyphs = map.cells
+            .iter()
+            .map(|(char, (page, cell))| (*char, Glyph {
+                x: ((cell % map.columns) * map.cell_width) as i32,
+                y: ((cell / map.columns) * map.cell_height) as i32,
+                width: map.cell_width,
+                height: map.cell_height,
+                offset_x: 0,
+                offset_y: 0,
+                advance: map.cell_width as i32,
+                page: *page,
+            }))
+            .collect();
+
+        Font {
+            line_height: map.cell_height,
+            base: map.cell_height,
+            replacement: map.replacement,
+            glyphs,
+            kernings: HashMap::new(),
+        }
+    }
+
+    // parses an AngelCode BMFont descriptor in the text format.
+    pub fn from_fnt(text: &str) -> Result<Self> {
+        if text.starts_with("BMF") {
+            return Err(font_error("binary BMFont files are not supported, export the descriptor as text"));
+        }
+
+        let mut font = Font {
+            line_height: 0,
+            base: 0,
+            replacement: None,
+            glyphs: HashMap::new(),
+            kernings: HashMap::new(),
+        };
+
+        for line in text.lines() {
+            let line = line.trim();
+            let (tag, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let pairs = fnt_pairs(rest);
+
+            match tag {
+                "common" => {
+                    font.line_height = fnt_value(&pairs, "lineHeight")?;
+                    font.base = fnt_value(&pairs, "base")?;
+                },
+                "char" => {
+                    let glyph = Glyph {
+                        x: fnt_value(&pairs, "x")?,
+                        y: fnt_value(&pairs, "y")?,
+                        width: fnt_value(&pairs, "width")?,
+                        height: fnt_value(&pairs, "height")?,
+                        offset_x: fnt_value(&pairs, "xoffset")?,
+                        offset_y: fnt_value(&pairs, "yoffset")?,
+                        advance: fnt_value(&pairs, "xadvance")?,
+                        page: fnt_value(&pairs, "page")?,
+                    };
+                    font.glyphs.insert(fnt_char(&pairs, "id")?, glyph);
+                },
+                "kerning" => {
+                    let first = fnt_char(&pairs, "first")?;
+                    let second = fnt_char(&pairs, "second")?;
+                    font.kernings.insert((first, second), fnt_value(&pairs, "amount")?);
+                },
+                _ => {},
+            }
+        }
+
+        if font.line_height == 0 {
+            return Err(font_error("missing `common` line"));
+        }
+        font.replacement = ['\u{FFFD}', '?'].into_iter().find(|char| font.glyphs.contains_key(char));
+        Ok(font)
+    }
+
+    pub fn glyph(&self, char: char) -> Option<&Glyph> {
+        self.glyphs.get(&char)
+    }
+
+    // the glyph of char, or of the replacement character when the font lacks it.
+    // control characters have no glyph.
+    pub fn glyph_or_replacement(&self, char: char) -> Option<&Glyph> {
+        if char.is_control() {
+            return None;
+        }
+        self.glyph(char).or_else(|| self.replacement.and_then(|replacement| self.glyph(replacement)))
+    }
+
+    pub fn kerning(&self, first: char, second: char) -> i32 {
+        self.kernings.get(&(first, second)).cloned().unwrap_or(0)
+    }
+
+    // pen position of every drawable character of a single line, unscaled.
+    // characters without a glyph or replacement are skipped.
+    pub fn layout<'f>(&'f self, text: &str) -> Vec<(i32, &'f Glyph)> {
+        let mut pen = 0;
+        let mut previous = None;
+        let mut placed = Vec::new();
+
+        for char in text.chars() {
+            let glyph = match self.glyph_or_replacement(char) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if let Some(previous) = previous {
+                pen += self.kerning(previous, char);
+            }
+            placed.push((pen, glyph));
+            pen += glyph.advance;
+            previous = Some(char);
+        }
+
+        placed
+    }
+
+    pub fn text_width(&self, text: &str) -> u32 {
+        self.layout(text)
+            .last()
+            .map_or(0, |(pen, glyph)| (pen + glyph.advance.max(glyph.offset_x + glyph.width as i32)).max(0) as u32)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+// how text is laid out inside a box by RenderContext::draw_text_box.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextLayout {
+    pub align: TextAlign,
+    // extra pixels between lines, before scaling. may be negative.
+    pub line_spacing: i32,
+    // breaks lines between words to fit the box width.
+    pub wrap: bool,
+}
+
+impl Default for TextLayout {
+    fn default() -> Self {
+        TextLayout {
+            align: TextAlign::Left,
+            line_spacing: 0,
+            wrap: true,
+        }
+    }
+}
+
+impl TextLayout {
+    pub fn new() -> Self {
+        TextLayout::default()
+    }
+
+    pub fn align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn line_spacing(mut self, line_spacing: i32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+}
+
+impl Font {
+    // splits text at newlines and, with a max width, between words. a word
+    // wider than the max width is split between characters.
+    pub fn wrap_lines(&self, text: &str, max_width: Option<u32>) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.split('\n').map(|line| line.trim_end_matches('\r')) {
+            let max_width = match max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(paragraph.to_string());
+                    continue;
+                },
+            };
+
+            let mut line = String::new();
+            for word in paragraph.split(' ') {
+                let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+                if self.text_width(&candidate) <= max_width {
+                    line = candidate;
+                    continue;
+                }
+                if !line.is_empty() {
+                    lines.push(line);
+                }
+                line = String::new();
+                for char in word.chars() {
+                    line.push(char);
+                    if self.text_width(&line) > max_width && line.chars().count() > 1 {
+                        line.pop();
+                        lines.push(line);
+                        line = char.to_string();
+                    }
+                }
+            }
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    pub fn line_advance(&self, layout: &TextLayout) -> i32 {
+        self.line_height as i32 + layout.line_spacing
+    }
+
+    // size of the laid out text, unscaled.
+    pub fn measure(&self, text: &str, max_width: Option<u32>, layout: &TextLayout) -> (u32, u32) {
+        let lines = self.wrap_lines(text, if layout.wrap { max_width } else { None });
+        let width = lines.iter().map(|line| self.text_width(line)).max().unwrap_or(0);
+        let height = (self.line_advance(layout) * (lines.len() as i32 - 1) + self.line_height as i32).max(0);
+        (width, height as u32)
+    }
+}
+
+// how text is drawn. the color multiplies the font image, so tinting needs
+// white glyphs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextStyle<Id> {
+    // None for the default font.
+    pub font: Option<Id>,
+    pub scale: f32,
+    pub color: Color,
+    // applies to the text, its shadow and its outline.
+    pub alpha: u8,
+    pub shadow_offset: (i32, i32),
+    pub shadow_color: Option<Color>,
+    pub outline_width: u32,
+    pub outline_color: Option<Color>,
+}
+
+impl<Id> Default for TextStyle<Id> {
+    fn default() -> Self {
+        TextStyle {
+            font: None,
+            scale: 1.0,
+            color: Color::WHITE,
+            alpha: 255,
+            shadow_offset: (1, 1),
+            shadow_color: None,
+            outline_width: 0,
+            outline_color: None,
+        }
+    }
+}
+
+impl<Id> TextStyle<Id> {
+    pub fn new() -> Self {
+        TextStyle::default()
+    }
+
+    pub fn font(mut self, font: Id) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn shadow(mut self, offset_x: i32, offset_y: i32, color: Color) -> Self {
+        self.shadow_offset = (offset_x, offset_y);
+        self.shadow_color = Some(color);
+        self
+    }
+
+    pub fn outline(mut self, width: u32, color: Color) -> Self {
+        self.outline_width = width;
+        self.outline_color = Some(color);
+        self
+    }
+
+    // offset and color of every copy of the text, back to front.
+    pub(crate) fn passes(&self) -> Vec<(i32, i32, Color)> {
+        let with_alpha = |color: Color| Color::RGBA(color.r, color.g, color.b, (color.a as u32 * self.alpha as u32 / 255) as u8);
+        let mut passes = Vec::new();
+
+        if let Some(color) = self.shadow_color {
+            passes.push((self.shadow_offset.0, self.shadow_offset.1, with_alpha(color)));
+        }
+        if let Some(color) = self.outline_color {
+            let width = self.outline_width as i32;
+            for y in -width..=width {
+                for x in -width..=width {
+                    if (x, y) != (0, 0) {
+                        passes.push((x, y, with_alpha(color)));
+                    }
+                }
+            }
+        }
+        passes.push((0, 0, with_alpha(self.color)));
+
+        passes
+    }
+
+    // pixels the shadow and outline add around the text: left, top, right, bottom.
+    pub fn padding(&self) -> (u32, u32, u32, u32) {
+        let outline = if self.outline_color.is_some() { self.outline_width as i32 } else { 0 };
+        let (shadow_x, shadow_y) = if self.shadow_color.is_some() { self.shadow_offset } else { (0, 0) };
+        (
+            outline.max(-shadow_x) as u32,
+            outline.max(-shadow_y) as u32,
+            outline.max(shadow_x) as u32,
+            outline.max(shadow_y) as u32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FNT: &str = r#"info face="Pixel Sans" size=8 bold=0 padding=0,0,0,0 spacing=1,1
+common lineHeight=10 base=8 scaleW=64 scaleH=64 pages=2 packed=0
+page id=0 file="pixel_0.png"
+page id=1 file="pixel_1.png"
+chars count=3
+char id=65   x=0    y=0    width=6    height=7    xoffset=0    yoffset=1    xadvance=7    page=0  chnl=15
+char id=86   x=7    y=0    width=6    height=7    xoffset=-1   yoffset=1    xadvance=6    page=0  chnl=15
+char id=63   x=0    y=0    width=5    height=7    xoffset=0    yoffset=1    xadvance=6    page=1  chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-2
+"#;
+
+    #[test]
+    fn splits_fnt_pairs() {
+        let pairs = fnt_pairs(r#"face="Pixel Sans" size=8  charset="" spacing=1,1"#);
+        assert_eq!(pairs["face"], "Pixel Sans");
+        assert_eq!(pairs["size"], "8");
+        assert_eq!(pairs["charset"], "");
+        assert_eq!(pairs["spacing"], "1,1");
+        assert!(fnt_pairs("").is_empty());
+    }
+
+    #[test]
+    fn parses_fnt() {
+        let font = Font::from_fnt(FNT).unwrap();
+
+        assert_eq!((font.line_height, font.base), (10, 8));
+        assert_eq!(font.glyph('V'), Some(&Glyph {
+            x: 7, y: 0, width: 6, height: 7, offset_x: -1, offset_y: 1, advance: 6, page: 0,
+        }));
+        assert_eq!(font.glyph('?').map(|glyph| glyph.page), Some(1));
+        assert_eq!(font.kerning('A', 'V'), -2);
+        assert_eq!(font.kerning('V', 'A'), 0);
+
+        assert_eq!(font.replacement, Some('?'));
+        assert_eq!(font.glyph_or_replacement('z'), font.glyph('?'));
+        assert_eq!(font.glyph_or_replacement('\n'), None);
+
+        let pens: Vec<i32> = font.layout("AV").iter().map(|(pen, _)| *pen).collect();
+        assert_eq!(pens, vec![0, 5]);
+        assert_eq!(font.text_width("AV"), 11);
+    }
+
+    #[test]
+    fn rejects_broken_fnt() {
+        assert!(Font::from_fnt("BMF\u{3}").is_err());
+        assert!(Font::from_fnt("char id=65 x=0 y=0 width=6 height=7").is_err());
+        assert!(Font::from_fnt("common lineHeight=ten base=8").is_err());
+        assert!(Font::from_fnt("common lineHeight=10\nchar id=55296 width=1").is_err());
+    }
+}
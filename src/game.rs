@@ -1,17 +1,51 @@
 use specs::{World, WorldExt};
+use sdl2::controller::GameController;
+use sdl2::event::Event;
+use sdl2::joystick::Joystick;
 use sdl2::keyboard::Keycode;
-use sdl2::{EventPump, Sdl, TimerSubsystem};
+use sdl2::{EventPump, GameControllerSubsystem, JoystickSubsystem, Sdl, TimerSubsystem};
+use std::collections::HashMap;
 use std::time::Duration;
 use crate::audio::{AudioContext, initialize_sounds};
-use crate::input::{initialize_input, InputContext};
+use crate::bindings::{KeyBindings, PhysicalInput};
+use crate::camera::{Camera, update_camera};
+use crate::combo::{ComboMatcher, ComboQueue, InputBuffer, KeyTransition};
+use crate::config::GameConfig;
+use crate::error::{Error, Result};
+use crate::input::{initialize_input, InputContext, Keys};
+use crate::listener::AudioListener;
 use crate::components::register_components;
 use crate::{MainLoop, ImageResource, SoundResource};
+use crate::replay::{InputFrame, InputRecorder, InputReplay};
 use crate::resources::{Core, InputQueue, register_resources, Time};
-use crate::texture::{initialize_render, RenderContext};
+use crate::texture::{initialize_render, RenderContext, RenderMode};
 
 const NANO_1SEC: u32 = 1_000_000_000u32;
 const NANO_1SEC_F64: f64 = NANO_1SEC as f64;
-const NANO_FRAME_SEC: u32 = NANO_1SEC / 60;
+const DEFAULT_MAX_TICKS_PER_FRAME: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum UpdateMode {
+    // MainLoop::update runs exactly once per rendered frame.
+    #[default]
+    Variable,
+    // MainLoop::update runs at a fixed rate, independent of the render rate.
+    // at most max_ticks_per_frame updates run per rendered frame; any time
+    // beyond that is dropped so a slow frame can't snowball.
+    Fixed {
+        ticks_per_second: u32,
+        max_ticks_per_frame: u32,
+    },
+}
+
+impl UpdateMode {
+    pub fn fixed(ticks_per_second: u32) -> Self {
+        UpdateMode::Fixed {
+            ticks_per_second,
+            max_ticks_per_frame: DEFAULT_MAX_TICKS_PER_FRAME,
+        }
+    }
+}
 
 pub struct Game<'a, IR: ImageResource + Default, SR: SoundResource<'a> + Default> {
     pub world: World,
@@ -19,15 +53,42 @@ pub struct Game<'a, IR: ImageResource + Default, SR: SoundResource<'a> + Default
     pub render_context: RenderContext<IR>,
     pub input_context: InputContext,
     pub timer_subsystem: TimerSubsystem,
+    pub controller_subsystem: GameControllerSubsystem,
+    pub joystick_subsystem: JoystickSubsystem,
+    controllers: HashMap<u32, GameController>,
+    joysticks: HashMap<u32, Joystick>,
     pub main_loop: Box<dyn MainLoop<'a, IR, SR>>,
+    pub update_mode: UpdateMode,
+    pub config: GameConfig,
+    frame_nano: u32,
+    accumulator_nano: u64,
+    last_update_counter: u64,
+    recorder: Option<InputRecorder>,
+    // why recording stopped early, returned by stop_recording.
+    recording_error: Option<Error>,
+    replay: Option<InputReplay>,
+    replay_frame: Option<InputFrame>,
+    // added to the timer so the clock keeps counting from where a replay left
+    // it instead of jumping back to the real time.
+    ticks_offset: i64,
+    input_frame: InputFrame,
     sdl_context: Sdl,
 }
 
 impl<'a, IR: ImageResource + Default + 'static, SR: SoundResource<'a> + Default + 'static + Copy> Game<'a, IR, SR> {
-    pub fn new(main_loop: impl MainLoop<'a, IR, SR> + 'static, image_resource: IR, sound_resource: SR)
-        -> Self where <IR as ImageResource>::TextureId: Send + Sync {
+    pub fn new(main_loop: impl MainLoop<'a, IR, SR> + 'static, image_resource: IR, sound_resource: SR, config: GameConfig)
+        -> Result<Self> where <IR as ImageResource>::TextureId: Send + Sync {
+
+        if config.render_mode == RenderMode::Headless {
+            // must be set before SDL is initialized. SDL before 2.0.22 only
+            // reads the environment variables, later versions also the hints.
+            std::env::set_var("SDL_VIDEODRIVER", "dummy");
+            std::env::set_var("SDL_AUDIODRIVER", "dummy");
+            sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+            sdl2::hint::set("SDL_AUDIODRIVER", "dummy");
+        }
 
-        let sdl_context = sdl2::init().unwrap();
+        let sdl_context = sdl2::init().map_err(Error::Sdl)?;
         let main_loop = Box::new(main_loop);
 
         let mut world: World = main_loop.create_world();
@@ -36,43 +97,203 @@ impl<'a, IR: ImageResource + Default + 'static, SR: SoundResource<'a> + Default
 
         main_loop.post_create_world(&mut world);
 
-        let audio_context = initialize_sounds::<SR>(sound_resource);
-        let render_context = initialize_render::<IR>(&sdl_context, image_resource);
+        let audio_context = initialize_sounds::<SR>(sound_resource, &config)?;
+        let render_context = initialize_render::<IR>(&sdl_context, image_resource, &config)?;
+        world.write_resource::<Camera>().set_viewport_size(render_context.screen_width, render_context.screen_height);
+        world.write_resource::<AudioListener>().set_screen_width(render_context.screen_width);
         let input_context = initialize_input();
-        let timer_subsystem = sdl_context.timer().unwrap();
+        let timer_subsystem = sdl_context.timer().map_err(Error::Sdl)?;
+        let controller_subsystem = sdl_context.game_controller().map_err(Error::Sdl)?;
+        let joystick_subsystem = sdl_context.joystick().map_err(Error::Sdl)?;
+        let update_mode = main_loop.update_mode();
+        let frame_nano = NANO_1SEC / config.fps;
+        let recorder = match &config.record_input {
+            Some(path) => Some(InputRecorder::create(path)?),
+            None => None,
+        };
+        let replay = match &config.replay_input {
+            Some(path) => Some(InputReplay::open(path)?),
+            None => None,
+        };
 
-        Game {
+        Ok(Game {
             world,
             audio_context,
             render_context,
             input_context,
             timer_subsystem,
+            controller_subsystem,
+            joystick_subsystem,
+            controllers: HashMap::new(),
+            joysticks: HashMap::new(),
             main_loop,
+            update_mode,
+            config,
+            frame_nano,
+            accumulator_nano: 0,
+            last_update_counter: 0,
+            recorder,
+            recording_error: None,
+            replay,
+            replay_frame: None,
+            ticks_offset: 0,
+            input_frame: InputFrame::default(),
             sdl_context,
-        }
+        })
     }
 
-    pub fn get_event_pump(&self) -> EventPump {
-        self.sdl_context.event_pump().unwrap()
+    pub fn get_event_pump(&self) -> Result<EventPump> {
+        self.sdl_context.event_pump().map_err(Error::Sdl)
     }
 
     pub fn setup(&mut self) {
         self.main_loop.setup(&mut self.render_context, &mut self.audio_context);
     }
 
+    pub fn start_recording<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<()> {
+        self.recorder = Some(InputRecorder::create(path)?);
+        self.recording_error = None;
+        Ok(())
+    }
+
+    // flushes the recording. fails if a frame could not be written, in which
+    // case the recording ends at that frame.
+    pub fn stop_recording(&mut self) -> Result<()> {
+        if let Some(error) = self.recording_error.take() {
+            return Err(error);
+        }
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // set when writing a frame failed and recording stopped.
+    pub fn recording_error(&self) -> Option<&Error> {
+        self.recording_error.as_ref()
+    }
+
+    pub fn start_replay(&mut self, replay: InputReplay) {
+        self.replay = Some(replay);
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
     pub fn update(&mut self) {
-        {
-            let mut input_queue = self.world.write_resource::<InputQueue>();
-            input_queue.keys_pressed.append(&mut self.input_context.get_keys_pressed());
-            input_queue.keys_pulled.append(&mut self.input_context.get_keys_pulled());
-            input_queue.keys_downed.clear();
-            input_queue.keys_downed.append(&mut self.input_context.get_keys_downed());
+        self.replay_frame = self.replay.as_mut().and_then(|replay| replay.next_frame());
+        if self.replay_frame.is_none() && self.replay.take().is_some() && self.config.exit_after_replay {
+            self.world.write_resource::<Core>().quit = true;
+            return;
         }
 
-        self.main_loop.update(&mut self.world, &mut self.audio_context);
+        let (keys_pressed, keys_pulled, keys_downed) = match &self.replay_frame {
+            Some(frame) => (frame.keys_pressed(), frame.keys_pulled(), frame.keys_downed()),
+            None => {
+                let bindings = self.world.read_resource::<KeyBindings>();
+                (
+                    self.input_context.get_keys_pressed(&bindings),
+                    self.input_context.get_keys_pulled(&bindings),
+                    self.input_context.get_keys_downed(&bindings),
+                )
+            },
+        };
+        self.input_frame = InputFrame::new(&keys_pressed, &keys_pulled, &keys_downed);
+
+        self.queue_input(keys_pressed, keys_pulled, keys_downed);
+
+        let ticks = match &self.replay_frame {
+            Some(frame) => frame.update_ticks as u32,
+            None => self.get_update_ticks(),
+        };
+        self.input_frame.update_ticks = ticks as u16;
+        for _ in 0..ticks {
+            self.main_loop.update(&mut self.world, &mut self.audio_context);
+        }
+        self.audio_context.update();
+
+        let mut time = self.world.write_resource::<Time>();
+        time.update_ticks += ticks as u64;
+        time.update_alpha = match self.update_mode {
+            UpdateMode::Variable => 1.0,
+            UpdateMode::Fixed { ticks_per_second, .. } => {
+                self.accumulator_nano as f64 / (NANO_1SEC / ticks_per_second) as f64
+            }
+        };
+    }
+
+    fn queue_input(&mut self, mut keys_pressed: Vec<Keys>, mut keys_pulled: Vec<Keys>, mut keys_downed: Vec<Keys>) {
+        let last_ticks = self.world.read_resource::<Time>().last_ticks;
+        let mut input_queue = self.world.write_resource::<InputQueue>();
+        input_queue.keys_downed.clear();
+        input_queue.keys_downed.append(&mut keys_downed);
+        input_queue.update_holds(&keys_pressed, last_ticks);
+
+        let mut input_buffer = self.world.write_resource::<InputBuffer>();
+        let mut combo_matcher = self.world.write_resource::<ComboMatcher>();
+        let mut combo_queue = self.world.write_resource::<ComboQueue>();
+        // presses first, so a key tapped within one frame still goes down before up.
+        let transitions = keys_pressed
+            .iter()
+            .map(|key| (key, true))
+            .chain(keys_pulled.iter().map(|key| (key, false)));
+        for (key, pressed) in transitions {
+            let transition = KeyTransition { key: *key, pressed, ticks: last_ticks };
+            input_buffer.push(transition);
+            combo_matcher.feed(&transition, &mut combo_queue);
+        }
+
+        input_queue.keys_pressed.append(&mut keys_pressed);
+        input_queue.keys_pulled.append(&mut keys_pulled);
+    }
+
+    fn get_update_ticks(&mut self) -> u32 {
+        let (ticks_per_second, max_ticks_per_frame) = match self.update_mode {
+            UpdateMode::Variable => return 1,
+            UpdateMode::Fixed { ticks_per_second, max_ticks_per_frame } => {
+                (ticks_per_second.max(1), max_ticks_per_frame.max(1))
+            }
+        };
+
+        let step = (NANO_1SEC / ticks_per_second) as u64;
+        let now = self.timer_subsystem.performance_counter();
+
+        if self.last_update_counter == 0 {
+            self.last_update_counter = now;
+            self.accumulator_nano = 0;
+            return 1;
+        }
+
+        let elapsed = (now - self.last_update_counter) as f64 / self.timer_subsystem.performance_frequency() as f64;
+        self.last_update_counter = now;
+
+        // never feed more time than the catch-up limit can consume.
+        let max_nano = step * max_ticks_per_frame as u64;
+        self.accumulator_nano += ((NANO_1SEC_F64 * elapsed) as u64).min(max_nano);
+
+        let mut ticks = 0;
+        while self.accumulator_nano >= step && ticks < max_ticks_per_frame {
+            self.accumulator_nano -= step;
+            ticks += 1;
+        }
+
+        if self.accumulator_nano >= step {
+            self.accumulator_nano %= step;
+        }
+
+        ticks
     }
 
     pub fn draw(&mut self) {
+        let last_ticks = self.world.read_resource::<Time>().last_ticks;
+        update_camera(&self.world, &self.render_context.resource, last_ticks);
+        self.render_context.view_offset = self.world.read_resource::<Camera>().offset();
+
         self.main_loop.draw(&mut self.world, &mut self.render_context);
     }
 
@@ -103,6 +324,14 @@ impl<'a, IR: ImageResource + Default + 'static, SR: SoundResource<'a> + Default
         self.main_loop.reset_frame(&mut self.world, &mut self.render_context, &mut self.audio_context);
     }
 
+    pub fn teardown(&mut self) {
+        self.main_loop.teardown(&mut self.world, &mut self.render_context, &mut self.audio_context);
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.world.read_resource::<Core>().quit
+    }
+
     pub fn on_key_down(&mut self, keycode: Keycode) {
         self.input_context.on_key_down(keycode);
     }
@@ -111,6 +340,65 @@ impl<'a, IR: ImageResource + Default + 'static, SR: SoundResource<'a> + Default
         self.input_context.on_key_up(keycode);
     }
 
+    // handles game controller and joystick events, including hot-plugging.
+    // SDL reports devices connected at startup as added too.
+    pub fn on_event(&mut self, event: &Event) {
+        let deadzone = self.world.read_resource::<KeyBindings>().axis_deadzone;
+
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Ok(controller) = self.controller_subsystem.open(which) {
+                    self.controllers.insert(controller.instance_id(), controller);
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers.remove(&which);
+                self.input_context.release_devices();
+            },
+            Event::ControllerButtonDown { button, .. } => {
+                self.input_context.on_input_down(PhysicalInput::ControllerButton(button));
+            },
+            Event::ControllerButtonUp { button, .. } => {
+                self.input_context.on_input_up(PhysicalInput::ControllerButton(button));
+            },
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                self.input_context.on_axis_motion(
+                    PhysicalInput::ControllerAxis(axis, false),
+                    PhysicalInput::ControllerAxis(axis, true),
+                    value,
+                    deadzone);
+            },
+            // devices with a controller mapping are opened as controllers instead.
+            Event::JoyDeviceAdded { which, .. } if !self.controller_subsystem.is_game_controller(which) => {
+                if let Ok(joystick) = self.joystick_subsystem.open(which) {
+                    self.joysticks.insert(joystick.instance_id(), joystick);
+                }
+            },
+            Event::JoyDeviceRemoved { which, .. } if self.joysticks.contains_key(&which) => {
+                self.joysticks.remove(&which);
+                self.input_context.release_devices();
+            },
+            // game controllers send joystick events too, skip those.
+            Event::JoyButtonDown { which, button_idx, .. } if self.joysticks.contains_key(&which) => {
+                self.input_context.on_input_down(PhysicalInput::JoyButton(button_idx));
+            },
+            Event::JoyButtonUp { which, button_idx, .. } if self.joysticks.contains_key(&which) => {
+                self.input_context.on_input_up(PhysicalInput::JoyButton(button_idx));
+            },
+            Event::JoyAxisMotion { which, axis_idx, value, .. } if self.joysticks.contains_key(&which) => {
+                self.input_context.on_axis_motion(
+                    PhysicalInput::JoyAxis(axis_idx, false),
+                    PhysicalInput::JoyAxis(axis_idx, true),
+                    value,
+                    deadzone);
+            },
+            Event::JoyHatMotion { which, hat_idx, state, .. } if self.joysticks.contains_key(&which) => {
+                self.input_context.on_hat_motion(hat_idx, state);
+            },
+            _ => {}
+        }
+    }
+
     pub fn sleep_frame(&mut self) {
         let mut time = self.world.write_resource::<Time>();
         let core = self.world.read_resource::<Core>();
@@ -124,12 +412,28 @@ impl<'a, IR: ImageResource + Default + 'static, SR: SoundResource<'a> + Default
         let elapsed = (*end - *start) as f64 / self.timer_subsystem.performance_frequency() as f64;
         let nano_sec = (NANO_1SEC_F64 * elapsed) as u32;
 
-        if nano_sec < NANO_FRAME_SEC {
-            std::thread::sleep(Duration::new(0, NANO_FRAME_SEC - nano_sec));
+        if nano_sec < self.frame_nano {
+            std::thread::sleep(Duration::new(0, self.frame_nano - nano_sec));
         }
 
         time.last_performance_counter = self.timer_subsystem.performance_counter();
-        time.last_ticks = self.timer_subsystem.ticks();
+        let timer_ticks = self.timer_subsystem.ticks() as i64;
+        time.last_ticks = match &self.replay_frame {
+            // replays run on the recorded clock so timers behave the same.
+            Some(frame) => {
+                let ticks = time.prev_ticks + frame.elapsed_ms;
+                self.ticks_offset = ticks as i64 - timer_ticks;
+                ticks
+            },
+            None => (timer_ticks + self.ticks_offset).max(time.prev_ticks as i64) as u32,
+        };
+        self.input_frame.elapsed_ms = time.last_ticks - time.prev_ticks;
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(error) = recorder.record(&self.input_frame) {
+                self.recorder = None;
+                self.recording_error = Some(error);
+            }
+        }
         time.fps_ticks_cache += time.last_ticks - time.prev_ticks;
 
         if !core.pause_time {
@@ -1,7 +1,9 @@
 use std::collections::HashSet;
+use sdl2::joystick::HatState;
 use sdl2::keyboard::Keycode;
+use crate::bindings::{KeyBindings, PhysicalInput};
 
-#[derive(Eq, Hash, PartialEq, Clone, Copy)]
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, PartialOrd, Ord)]
 pub enum Keys {
     Up,
     Down,
@@ -15,10 +17,47 @@ pub enum Keys {
     Start,
 }
 
+impl Keys {
+    pub const ALL: [Keys; 10] = [
+        Keys::Up,
+        Keys::Down,
+        Keys::Left,
+        Keys::Right,
+        Keys::X,
+        Keys::Y,
+        Keys::A,
+        Keys::B,
+        Keys::Select,
+        Keys::Start,
+    ];
+
+    pub(crate) fn bit(&self) -> u16 {
+        1 << (*self as u16)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Keys::Up => "Up",
+            Keys::Down => "Down",
+            Keys::Left => "Left",
+            Keys::Right => "Right",
+            Keys::X => "X",
+            Keys::Y => "Y",
+            Keys::A => "A",
+            Keys::B => "B",
+            Keys::Select => "Select",
+            Keys::Start => "Start",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Keys> {
+        Keys::ALL.iter().find(|key| key.name() == name).cloned()
+    }
+}
+
 mod keys_context {
     use std::collections::HashMap;
     use lazy_static::lazy_static;
-    use sdl2::keyboard::Keycode;
     use input::Keys;
     use crate::input;
 
@@ -35,91 +74,104 @@ mod keys_context {
             (Keys::Select,      "[]".to_string()),
             (Keys::Start,       "[>".to_string()),
         ].into_iter().collect();
-
-        pub static ref KEYS_MAP: HashMap<Keycode, Keys> = [
-            (Keycode::Up,       Keys::Up),
-            (Keycode::Down,     Keys::Down),
-            (Keycode::Left,     Keys::Left),
-            (Keycode::Right,    Keys::Right),
-            (Keycode::U,        Keys::X),
-            (Keycode::I,        Keys::Y),
-            (Keycode::J,        Keys::A),
-            (Keycode::K,        Keys::B),
-            (Keycode::Space,    Keys::Select),
-            (Keycode::Return,   Keys::Start),
-        ].into_iter().collect();
     }
 }
 
 pub struct InputContext {
-    keys_down: HashSet<Keycode>,
-    keys_up: HashSet<Keycode>,
-    keys_pressed: HashSet<Keycode>,
-    keys_pulled: HashSet<Keycode>,
+    inputs_down: HashSet<PhysicalInput>,
+    inputs_pressed: HashSet<PhysicalInput>,
+    inputs_pulled: HashSet<PhysicalInput>,
 }
 
 impl InputContext {
     pub fn new() -> Self {
-        let ref keys_map = keys_context::KEYS_MAP;
         InputContext {
-            keys_down: HashSet::new(),
-            keys_up: HashSet::from_iter(keys_map.keys().cloned()),
-            keys_pressed: HashSet::new(),
-            keys_pulled: HashSet::new(),
+            inputs_down: HashSet::new(),
+            inputs_pressed: HashSet::new(),
+            inputs_pulled: HashSet::new(),
         }
     }
 
     pub fn reset_frame(&mut self) {
-        self.keys_pulled.clear();
-        self.keys_pressed.clear();
+        self.inputs_pulled.clear();
+        self.inputs_pressed.clear();
     }
 
     pub fn on_key_down(&mut self, keycode: Keycode) {
-        let ref keys_map = keys_context::KEYS_MAP;
-        if !keys_map.contains_key(&keycode) {
-            return;
-        }
-        if !self.keys_down.contains(&keycode) && self.keys_up.contains(&keycode) {
-            self.keys_pressed.insert(keycode.clone());
-        }
-        self.keys_down.insert(keycode.clone());
-        self.keys_up.remove(&keycode);
+        self.on_input_down(PhysicalInput::Key(keycode));
     }
 
     pub fn on_key_up(&mut self, keycode: Keycode) {
-        let ref keys_map = keys_context::KEYS_MAP;
-        if !keys_map.contains_key(&keycode) {
-            return;
+        self.on_input_up(PhysicalInput::Key(keycode));
+    }
+
+    // every physical input is tracked, so bindings can change at any time.
+    pub fn on_input_down(&mut self, input: PhysicalInput) {
+        if self.inputs_down.insert(input) {
+            self.inputs_pressed.insert(input);
         }
-        if self.keys_down.contains(&keycode) && !self.keys_up.contains(&keycode) {
-            self.keys_pulled.insert(keycode.clone());
+    }
+
+    pub fn on_input_up(&mut self, input: PhysicalInput) {
+        if self.inputs_down.remove(&input) {
+            self.inputs_pulled.insert(input);
         }
-        self.keys_up.insert(keycode.clone());
-        self.keys_down.remove(&keycode);
     }
 
-    pub fn get_keys_pressed(&mut self) -> Vec<Keys> {
-        let ref keys_map = keys_context::KEYS_MAP;
-        self.keys_pressed
-            .iter()
-            .map(|key| keys_map.get(key).unwrap().clone())
-            .collect()
+    fn set_input(&mut self, input: PhysicalInput, down: bool) {
+        if down {
+            self.on_input_down(input);
+        } else {
+            self.on_input_up(input);
+        }
     }
 
-    pub fn get_keys_pulled(&mut self) -> Vec<Keys> {
-        let ref keys_map = keys_context::KEYS_MAP;
-        self.keys_pulled
-            .iter()
-            .map(|key| keys_map.get(key).unwrap().clone())
-            .collect()
+    pub fn on_axis_motion(&mut self, negative: PhysicalInput, positive: PhysicalInput, value: i16, deadzone: i16) {
+        self.set_input(negative, value < -deadzone);
+        self.set_input(positive, value > deadzone);
+    }
+
+    pub fn on_hat_motion(&mut self, hat: u8, state: HatState) {
+        let (up, down, left, right) = match state {
+            HatState::Centered => (false, false, false, false),
+            HatState::Up => (true, false, false, false),
+            HatState::Down => (false, true, false, false),
+            HatState::Left => (false, false, true, false),
+            HatState::Right => (false, false, false, true),
+            HatState::LeftUp => (true, false, true, false),
+            HatState::RightUp => (true, false, false, true),
+            HatState::LeftDown => (false, true, true, false),
+            HatState::RightDown => (false, true, false, true),
+        };
+        self.set_input(PhysicalInput::JoyHat(hat, Keys::Up), up);
+        self.set_input(PhysicalInput::JoyHat(hat, Keys::Down), down);
+        self.set_input(PhysicalInput::JoyHat(hat, Keys::Left), left);
+        self.set_input(PhysicalInput::JoyHat(hat, Keys::Right), right);
     }
 
-    pub fn get_keys_downed(&mut self) -> Vec<Keys> {
-        let ref keys_map = keys_context::KEYS_MAP;
-        self.keys_down
+    // releases everything held on game controllers and joysticks, e.g. when one is unplugged.
+    pub fn release_devices(&mut self) {
+        let inputs = self.inputs_down
             .iter()
-            .map(|key| keys_map.get(key).unwrap().clone())
-            .collect()
+            .filter(|input| !matches!(input, PhysicalInput::Key(_)))
+            .cloned()
+            .collect::<Vec<PhysicalInput>>();
+        for input in inputs {
+            self.on_input_up(input);
+        }
+    }
+
+    // the results are sorted, HashSet order is random and replays need a stable one.
+    pub fn get_keys_pressed(&mut self, bindings: &KeyBindings) -> Vec<Keys> {
+        bindings.map_keys(self.inputs_pressed.iter())
+    }
+
+    pub fn get_keys_pulled(&mut self, bindings: &KeyBindings) -> Vec<Keys> {
+        bindings.map_keys(self.inputs_pulled.iter())
+    }
+
+    pub fn get_keys_downed(&mut self, bindings: &KeyBindings) -> Vec<Keys> {
+        bindings.map_keys(self.inputs_down.iter())
     }
 }
 
@@ -2,24 +2,55 @@ use std::hash::Hash;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use specs::World;
-use crate::game::Game;
 
+pub use crate::atlas::{Atlas, AtlasFrame};
+pub use crate::camera::Camera;
+pub use crate::game::{Game, UpdateMode};
+pub use crate::config::GameConfig;
+pub use crate::error::{Error, Result};
+pub use crate::font::{Font, Glyph, GlyphMap, TextAlign, TextLayout, TextStyle};
 pub use crate::render::RenderingHelper;
 pub use crate::constants::{SCREEN_WIDTH, SCREEN_HEIGHT};
-pub use crate::components::{Position, Renderable};
-pub use crate::resources::{Core, Time, InputQueue};
-pub use crate::audio::AudioContext;
-pub use crate::texture::RenderContext;
+pub use crate::components::{Animation, LoopMode, Position, Renderable, Sprite, Transform};
+pub use crate::resources::{Core, Time, InputQueue, RepeatSettings};
+pub use crate::audio::{AudioContext, SoundKind};
+pub use crate::audio_format::AudioFormat;
+pub use crate::volume::VolumeSettings;
+pub use crate::listener::AudioListener;
+pub use crate::texture::{RenderContext, RenderMode};
+pub use crate::text_cache::TextCache;
 pub use crate::input::{Keys, get_keys_text};
+pub use crate::bindings::{KeyBindings, PhysicalInput};
+pub use crate::combo::{Combo, ComboMatch, ComboMatcher, ComboQueue, InputBuffer, KeyTransition};
+pub use crate::replay::{InputFrame, InputRecorder, InputReplay};
+pub use crate::scene::{Scene, SceneCommand, SceneManager, Transition};
+#[cfg(feature = "tiled")]
+pub use crate::tilemap::{Layer, MapObject, ObjectLayer, Properties, PropertyValue, Tile, TileLayer, TileMap, Tileset};
 
+mod atlas;
 mod audio;
+mod audio_format;
+mod bindings;
+mod camera;
+mod combo;
 mod components;
+mod config;
 mod constants;
+mod error;
+mod font;
 mod game;
 mod input;
+mod listener;
+mod replay;
 mod resources;
+mod scene;
+mod settings_file;
+mod text_cache;
 mod texture;
 mod render;
+mod volume;
+#[cfg(feature = "tiled")]
+mod tilemap;
 
 pub trait ImageResource {
     type TextureId: Eq + Hash + PartialEq + Clone + Default;
@@ -32,7 +63,35 @@ pub trait ImageResource {
     fn get_font_height(&self, texture_id: &Self::TextureId) -> &u32;
     fn get_default_font_id(&self) -> Self::TextureId;
 
+    // AngelCode BMFont text descriptor (.fnt) of a font image. fonts without
+    // one are read as fixed-width rows of get_font_height squares.
+    fn get_font_descriptor(&self, _texture_id: &Self::TextureId) -> Option<&str> {
+        None
+    }
+
+    // style a text from get_text_ids is baked with.
+    fn get_text_style(&self, _texture_id: &Self::TextureId) -> TextStyle<Self::TextureId> {
+        TextStyle::default()
+    }
+
+    // cell layout of a grid font whose characters go beyond one ascii row.
+    // ignored for fonts with a descriptor.
+    fn get_glyph_map(&self, _texture_id: &Self::TextureId) -> Option<GlyphMap> {
+        None
+    }
+
+    // images holding each page of a font, in page order. a font is a single
+    // page, its own image, unless this says otherwise.
+    fn get_font_pages(&self, texture_id: &Self::TextureId) -> Vec<Self::TextureId> {
+        vec![texture_id.clone()]
+    }
+
     fn get_tile_position(&self, position: &Position) -> (i32, i32);
+
+    // frame rectangles of an image used as a sprite sheet, empty for plain images.
+    fn get_atlas_frames(&self, _texture_id: &Self::TextureId) -> Vec<AtlasFrame> {
+        Vec::new()
+    }
 }
 
 pub trait SoundResource<'a> {
@@ -40,6 +99,19 @@ pub trait SoundResource<'a> {
 
     fn get_audio_ids(&self) -> Vec<Self::AudioId>;
     fn get_audio(&self, audio_id: &Self::AudioId) -> &'static Vec<u8>;
+
+    // position in seconds a music track loops back to after its intro. for
+    // tracker modules it is the pattern number. the mixer cannot loop to a
+    // position by itself, so the track is restarted there on the next frame
+    // after it ends, leaving a short gap of up to a frame plus the seek.
+    fn get_loop_start(&self, _audio_id: &Self::AudioId) -> Option<f64> {
+        None
+    }
+
+    // everything loads as music unless marked as an effect.
+    fn get_sound_kind(&self, _audio_id: &Self::AudioId) -> SoundKind {
+        SoundKind::Music
+    }
 }
 
 pub trait MainLoop<'a, IR: ImageResource, SR: SoundResource<'a>> {
@@ -50,6 +122,15 @@ pub trait MainLoop<'a, IR: ImageResource, SR: SoundResource<'a>> {
     fn reset_game(&self, world: &mut World, render_context: &mut RenderContext<IR>, audio_context: &mut AudioContext<'a, SR>);
     fn reset_frame(&self, world: &mut World, render_context: &mut RenderContext<IR>, audio_context: &mut AudioContext<'a, SR>);
     fn draw(&self, world: &mut World, render_context: &mut RenderContext<IR>);
+
+    // called once when run exits.
+    fn teardown(&self, _world: &mut World, _render_context: &mut RenderContext<IR>, _audio_context: &mut AudioContext<'a, SR>) {
+        // empty
+    }
+
+    fn update_mode(&self) -> UpdateMode {
+        UpdateMode::Variable
+    }
 }
 
 pub fn run<
@@ -57,13 +138,13 @@ pub fn run<
         IR: ImageResource + Default + 'static,
         SR: SoundResource<'a> + Default + 'static + Copy,
         ML: MainLoop<'a, IR, SR> + 'static>
-    (main_loop: ML, image_resource: IR, sound_resource: SR)
+    (main_loop: ML, image_resource: IR, sound_resource: SR, config: GameConfig) -> Result<()>
     where <IR as ImageResource>::TextureId: Send + Sync {
 
-    let mut game = Game::new(main_loop, image_resource, sound_resource);
+    let mut game = Game::new(main_loop, image_resource, sound_resource, config)?;
     game.setup();
 
-    let mut event_pump = game.get_event_pump();
+    let mut event_pump = game.get_event_pump()?;
 
     'running: loop {
         game.reset_frame();
@@ -87,12 +168,23 @@ pub fn run<
                 } => {
                     game.on_key_up(keycode);
                 },
-                _ => {}
+                _ => game.on_event(&event),
             }
         }
 
         game.update();
         game.draw();
         game.sleep_frame();
+
+        if game.should_quit() {
+            break 'running;
+        }
     }
+
+    game.teardown();
+
+    // volumes are saved even when the recording could not be finished.
+    let recording = game.stop_recording();
+    game.audio_context.save_volume_settings()?;
+    recording
 }
\ No newline at end of file
@@ -0,0 +1,68 @@
+use specs::{Entity, World, WorldExt};
+use crate::camera::{Camera, entity_position};
+use crate::constants::SCREEN_WIDTH;
+use crate::ImageResource;
+
+// where positional sounds are heard from. positional audio is off until
+// enabled, and then AudioContext::play_effect_at and play_effect_from pan
+// and attenuate effects by their distance to the listener.
+#[derive(Clone, Copy, Debug)]
+pub struct AudioListener {
+    pub enabled: bool,
+    // None for the center of the screen.
+    pub entity: Option<Entity>,
+    // distance in pixels at which a sound drops to min_volume, None for the
+    // screen width.
+    pub range: Option<f32>,
+    // volume of sounds at or beyond range, so off-screen events stay audible.
+    pub min_volume: f32,
+    // horizontal distance in pixels at which a sound plays on one side only,
+    // None for the screen width.
+    pub pan_distance: Option<f32>,
+    screen_width: u32,
+}
+
+impl Default for AudioListener {
+    fn default() -> Self {
+        AudioListener {
+            enabled: false,
+            entity: None,
+            range: None,
+            min_volume: 0.3,
+            pan_distance: None,
+            screen_width: SCREEN_WIDTH,
+        }
+    }
+}
+
+impl AudioListener {
+    // left and right levels for Channel::set_panning of a sound at source
+    // heard from listener, both in world pixels.
+    pub fn panning(&self, listener: (f32, f32), source: (f32, f32)) -> (u8, u8) {
+        let (dx, dy) = (source.0 - listener.0, source.1 - listener.1);
+        let distance = (dx * dx + dy * dy).sqrt();
+        let range = self.range.unwrap_or(self.screen_width as f32);
+        let pan_distance = self.pan_distance.unwrap_or(self.screen_width as f32);
+        let falloff = if range > 0.0 { (distance / range).min(1.0) } else { 1.0 };
+        let volume = 1.0 - (1.0 - self.min_volume.clamp(0.0, 1.0)) * falloff;
+
+        let pan = if pan_distance > 0.0 { (dx / pan_distance).clamp(-1.0, 1.0) } else { 0.0 };
+        let left = volume * (1.0 - pan.max(0.0));
+        let right = volume * (1.0 + pan.min(0.0));
+        ((left * 255.0).round() as u8, (right * 255.0).round() as u8)
+    }
+
+    pub(crate) fn set_screen_width(&mut self, screen_width: u32) {
+        self.screen_width = screen_width;
+    }
+}
+
+pub(crate) fn listener_position<IR: ImageResource>(world: &World, listener: &AudioListener, resource: &IR) -> (f32, f32) {
+    listener.entity
+        .and_then(|entity| entity_position(world, entity, |position| resource.get_tile_position(position)))
+        .unwrap_or_else(|| {
+            let camera = world.read_resource::<Camera>();
+            let (width, height) = camera.viewport_size();
+            (camera.x + width as f32 / 2.0, camera.y + height as f32 / 2.0)
+        })
+}
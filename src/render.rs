@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use specs::{Join, Read, ReadStorage};
-use crate::components::{Position, Renderable, RenderableKind};
+use crate::components::{Animation, Position, Renderable, RenderableKind, Sprite, Transform};
 use crate::ImageResource;
 use crate::resources::Time;
 use crate::texture::RenderContext;
@@ -8,8 +8,11 @@ use crate::texture::RenderContext;
 struct DrawParam {
     pub x: i32,
     pub y: i32,
+    pub transform: Option<Transform>,
 }
 
+type RenderingData<'r, IR> = (Option<&'r Position>, Option<&'r Transform>, &'r Renderable<IR>, Option<&'r Animation>);
+
 pub struct RenderingHelper<'a, IR: ImageResource> {
     pub context: &'a mut RenderContext<IR>,
 }
@@ -17,6 +20,11 @@ pub struct RenderingHelper<'a, IR: ImageResource> {
 impl<IR: ImageResource> RenderingHelper<'_, IR> {
     pub fn get_texture_id(&self, renderable: &Renderable<IR>, ticks: u32)
                       -> IR::TextureId where <IR as ImageResource>::TextureId: Send + Sync {
+        self.get_sprite(renderable, ticks).id
+    }
+
+    pub fn get_sprite(&self, renderable: &Renderable<IR>, ticks: u32)
+                      -> Sprite<IR::TextureId> where <IR as ImageResource>::TextureId: Send + Sync {
         let id_index = match renderable.kind() {
             RenderableKind::Static => {
                 0
@@ -26,26 +34,70 @@ impl<IR: ImageResource> RenderingHelper<'_, IR> {
             }
         };
 
-        renderable.texture_id(id_index)
+        renderable.sprite(id_index)
     }
 
+    pub fn get_animated_sprite(&self, renderable: &Renderable<IR>, animation: Option<&Animation>, ticks: u32)
+                      -> Sprite<IR::TextureId> where <IR as ImageResource>::TextureId: Send + Sync {
+        match animation {
+            Some(animation) => renderable.sprite(animation.frame_index(ticks)),
+            None => self.get_sprite(renderable, ticks),
+        }
+    }
+
+    // draws every Renderable with a Position.
     pub fn draw_renderables(&mut self, positions: &ReadStorage<Position>, renderables: &ReadStorage<Renderable<IR>>, time: &Read<Time>)
         where <IR as ImageResource>::TextureId: Send + Sync {
 
-        let rendering_data = (positions, renderables).join().collect::<Vec<_>>();
-        let mut rendering_batches: HashMap<u8, HashMap<IR::TextureId, Vec<DrawParam>>> = HashMap::new();
+        let rendering_data = (positions, renderables)
+            .join()
+            .map(|(position, renderable)| (Some(position), None, renderable, None))
+            .collect::<Vec<_>>();
+        self.draw_batched(&rendering_data, time.last_ticks);
+    }
+
+    // draws every Renderable with a Position or a Transform, animated by its
+    // Animation if it has one.
+    pub fn draw_renderables_ex(
+        &mut self,
+        positions: &ReadStorage<Position>,
+        transforms: &ReadStorage<Transform>,
+        renderables: &ReadStorage<Renderable<IR>>,
+        animations: &ReadStorage<Animation>,
+        time: &Read<Time>)
+        where <IR as ImageResource>::TextureId: Send + Sync {
+
+        let rendering_data = (positions.maybe(), transforms.maybe(), renderables, animations.maybe()).join().collect::<Vec<_>>();
+        self.draw_batched(&rendering_data, time.last_ticks);
+    }
+
+    fn draw_batched(&mut self, rendering_data: &[RenderingData<IR>], ticks: u32)
+        where <IR as ImageResource>::TextureId: Send + Sync {
+
+        let (offset_x, offset_y) = self.context.view_offset;
+        let mut rendering_batches: HashMap<u8, HashMap<Sprite<IR::TextureId>, Vec<DrawParam>>> = HashMap::new();
 
-        for (position, renderable) in rendering_data.iter() {
-            let texture_id = self.get_texture_id(renderable, time.last_ticks);
+        for (position, transform, renderable, animation) in rendering_data.iter() {
+            let sprite = self.get_animated_sprite(renderable, *animation, ticks);
 
-            let (x, y) = self.context.resource.get_tile_position(position);
-            let z = position.z;
+            let (z, draw_param) = match (transform, position) {
+                (Some(transform), _) => {
+                    let mut transform = **transform;
+                    transform.x -= offset_x as f32;
+                    transform.y -= offset_y as f32;
+                    (transform.z, DrawParam { x: 0, y: 0, transform: Some(transform) })
+                },
+                (None, Some(position)) => {
+                    let (x, y) = self.context.resource.get_tile_position(position);
+                    (position.z, DrawParam { x: x - offset_x, y: y - offset_y, transform: None })
+                },
+                (None, None) => continue,
+            };
 
-            let draw_param = DrawParam { x, y };
             rendering_batches
                 .entry(z)
                 .or_default()
-                .entry(texture_id)
+                .entry(sprite)
                 .or_default()
                 .push(draw_param);
         }
@@ -54,9 +106,13 @@ impl<IR: ImageResource> RenderingHelper<'_, IR> {
         z_indexes.sort();
 
         for z in z_indexes.iter() {
-            for (texture_id, draw_params) in rendering_batches.get(&z).unwrap().iter() {
+            for (sprite, draw_params) in rendering_batches.get(&z).unwrap().iter() {
                 for draw_param in draw_params.iter() {
-                    self.context.draw(texture_id.clone(), draw_param.x, draw_param.y);
+                    match (&draw_param.transform, sprite.frame) {
+                        (Some(transform), frame) => self.context.draw_ex(sprite.id.clone(), frame, transform),
+                        (None, Some(frame)) => self.context.draw_frame(sprite.id.clone(), frame, draw_param.x, draw_param.y),
+                        (None, None) => self.context.draw(sprite.id.clone(), draw_param.x, draw_param.y),
+                    }
                 }
             }
         }
@@ -0,0 +1,215 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use crate::error::{Error, Result};
+use crate::input::Keys;
+
+// file layout: "GSIR", version byte, then one FRAME_SIZE record per frame.
+// version 1 stored elapsed_ms in 16 bits and is still read.
+const MAGIC: &[u8; 4] = b"GSIR";
+const VERSION: u8 = 2;
+const HEADER_SIZE: usize = 5;
+const FRAME_SIZE: usize = 12;
+const FRAME_SIZE_V1: usize = 10;
+
+fn keys_to_mask(keys: &[Keys]) -> u16 {
+    keys.iter().fold(0, |mask, key| mask | key.bit())
+}
+
+fn mask_to_keys(mask: u16) -> Vec<Keys> {
+    Keys::ALL
+        .iter()
+        .filter(|key| mask & key.bit() != 0)
+        .cloned()
+        .collect()
+}
+
+// everything the game received from outside during one frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct InputFrame {
+    keys_pressed: u16,
+    keys_pulled: u16,
+    keys_downed: u16,
+    pub update_ticks: u16,
+    pub elapsed_ms: u32,
+}
+
+impl InputFrame {
+    pub fn new(keys_pressed: &[Keys], keys_pulled: &[Keys], keys_downed: &[Keys]) -> Self {
+        InputFrame {
+            keys_pressed: keys_to_mask(keys_pressed),
+            keys_pulled: keys_to_mask(keys_pulled),
+            keys_downed: keys_to_mask(keys_downed),
+            update_ticks: 0,
+            elapsed_ms: 0,
+        }
+    }
+
+    pub fn keys_pressed(&self) -> Vec<Keys> {
+        mask_to_keys(self.keys_pressed)
+    }
+
+    pub fn keys_pulled(&self) -> Vec<Keys> {
+        mask_to_keys(self.keys_pulled)
+    }
+
+    pub fn keys_downed(&self) -> Vec<Keys> {
+        mask_to_keys(self.keys_downed)
+    }
+
+    fn to_bytes(self) -> [u8; FRAME_SIZE] {
+        let mut bytes = [0u8; FRAME_SIZE];
+        let fields = [self.keys_pressed, self.keys_pulled, self.keys_downed, self.update_ticks];
+        for (i, field) in fields.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&field.to_le_bytes());
+        }
+        bytes[8..12].copy_from_slice(&self.elapsed_ms.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let field = |i: usize| u16::from_le_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
+        let elapsed_ms = match bytes.len() {
+            FRAME_SIZE_V1 => field(4) as u32,
+            _ => u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        };
+        InputFrame {
+            keys_pressed: field(0),
+            keys_pulled: field(1),
+            keys_downed: field(2),
+            update_ticks: field(3),
+            elapsed_ms,
+        }
+    }
+}
+
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(InputRecorder { writer })
+    }
+
+    pub fn record(&mut self, frame: &InputFrame) -> Result<()> {
+        self.writer.write_all(&frame.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct InputReplay {
+    frames: Vec<InputFrame>,
+    position: usize,
+}
+
+impl InputReplay {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC {
+            return Err(Error::Replay("not an input recording".to_string()));
+        }
+        let frame_size = match bytes[4] {
+            1 => FRAME_SIZE_V1,
+            VERSION => FRAME_SIZE,
+            version => return Err(Error::Replay(format!("unsupported version {}", version))),
+        };
+
+        let body = &bytes[HEADER_SIZE..];
+        if !body.len().is_multiple_of(frame_size) {
+            return Err(Error::Replay("truncated frame".to_string()));
+        }
+
+        let frames = body
+            .chunks(frame_size)
+            .map(InputFrame::from_bytes)
+            .collect();
+
+        Ok(InputReplay {
+            frames,
+            position: 0,
+        })
+    }
+
+    pub fn next_frame(&mut self) -> Option<InputFrame> {
+        let frame = self.frames.get(self.position).cloned();
+        if frame.is_some() {
+            self.position += 1;
+        }
+        frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.frames.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(version: u8, frames: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(version);
+        for frame in frames {
+            bytes.extend_from_slice(frame);
+        }
+        bytes
+    }
+
+    #[test]
+    fn frame_round_trips_through_bytes() {
+        let mut frame = InputFrame::new(&[Keys::A, Keys::Up], &[Keys::B], &[Keys::A, Keys::Left]);
+        frame.update_ticks = 3;
+        frame.elapsed_ms = 70_000;
+
+        let bytes = recording(VERSION, &[&frame.to_bytes()]);
+        let mut replay = InputReplay::from_bytes(&bytes).unwrap();
+
+        let read = replay.next_frame().unwrap();
+        assert_eq!(read, frame);
+        assert_eq!(read.keys_pressed(), vec![Keys::Up, Keys::A]);
+        assert_eq!(read.keys_pulled(), vec![Keys::B]);
+        assert!(replay.is_finished());
+        assert_eq!(replay.next_frame(), None);
+    }
+
+    #[test]
+    fn reads_version_1_frames() {
+        let frame = [1, 0, 0, 0, 0, 0, 2, 0, 16, 0];
+        let mut replay = InputReplay::from_bytes(&recording(1, &[&frame])).unwrap();
+
+        let read = replay.next_frame().unwrap();
+        assert_eq!(read.update_ticks, 2);
+        assert_eq!(read.elapsed_ms, 16);
+    }
+
+    #[test]
+    fn rejects_broken_recordings() {
+        assert!(InputReplay::from_bytes(b"GSI").is_err());
+        assert!(InputReplay::from_bytes(&recording(9, &[])).is_err());
+        assert!(InputReplay::from_bytes(&recording(VERSION, &[&[0; 5]])).is_err());
+        assert!(InputReplay::from_bytes(&recording(VERSION, &[])).unwrap().is_empty());
+    }
+}
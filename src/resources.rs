@@ -1,17 +1,106 @@
+use std::collections::HashMap;
 use specs::World;
+use crate::bindings::KeyBindings;
+use crate::camera::Camera;
+use crate::combo::{ComboMatcher, ComboQueue, InputBuffer};
 use crate::input::Keys;
+use crate::listener::AudioListener;
 
 #[derive(Default)]
 pub struct InputQueue {
     pub keys_pressed: Vec<Keys>,
     pub keys_pulled: Vec<Keys>,
     pub keys_downed: Vec<Keys>,
+    // pressed keys, then held keys again every repeat interval, e.g. for menu cursors.
+    // like keys_pressed, these build up until the game drains them, so none
+    // are lost on frames without an update tick.
+    pub keys_repeated: Vec<Keys>,
+    // keys that have just been held for repeat.long_press_ms.
+    pub keys_long_pressed: Vec<Keys>,
+    pub repeat: RepeatSettings,
+    holds: HashMap<Keys, KeyHold>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RepeatSettings {
+    pub delay_ms: u32,
+    pub interval_ms: u32,
+    pub long_press_ms: u32,
+}
+
+impl Default for RepeatSettings {
+    fn default() -> Self {
+        RepeatSettings {
+            delay_ms: 400,
+            interval_ms: 100,
+            long_press_ms: 1000,
+        }
+    }
+}
+
+struct KeyHold {
+    start_ticks: u32,
+    frames: u32,
+    ms: u32,
+    next_repeat_ms: u32,
+    long_pressed: bool,
+}
+
+impl InputQueue {
+    // drops the queued key events. keys still down stay down.
+    pub fn clear(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_pulled.clear();
+        self.keys_repeated.clear();
+        self.keys_long_pressed.clear();
+    }
+
+    // frames the key has been down, 1 on the frame it was pressed and 0 if it is up.
+    pub fn hold_frames(&self, key: Keys) -> u32 {
+        self.holds.get(&key).map_or(0, |hold| hold.frames)
+    }
+
+    pub fn hold_ms(&self, key: Keys) -> u32 {
+        self.holds.get(&key).map_or(0, |hold| hold.ms)
+    }
+
+    pub(crate) fn update_holds(&mut self, keys_pressed: &[Keys], ticks: u32) {
+        self.keys_repeated.extend_from_slice(keys_pressed);
+        self.holds.retain(|key, _| self.keys_downed.contains(key));
+
+        for key in self.keys_downed.iter() {
+            let hold = self.holds.entry(*key).or_insert(KeyHold {
+                start_ticks: ticks,
+                frames: 0,
+                ms: 0,
+                next_repeat_ms: self.repeat.delay_ms,
+                long_pressed: false,
+            });
+            hold.frames += 1;
+            hold.ms = ticks - hold.start_ticks;
+
+            if hold.frames > 1 && hold.ms >= hold.next_repeat_ms {
+                self.keys_repeated.push(*key);
+                // at most one repeat per frame, even after a long frame.
+                while hold.next_repeat_ms <= hold.ms {
+                    hold.next_repeat_ms += self.repeat.interval_ms.max(1);
+                }
+            }
+
+            if !hold.long_pressed && hold.ms >= self.repeat.long_press_ms {
+                self.keys_long_pressed.push(*key);
+                hold.long_pressed = true;
+            }
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Core {
     pub pause_time: bool,
     pub reset: bool,
+    // ends the main loop after the current frame.
+    pub quit: bool,
 }
 
 #[derive(Default)]
@@ -26,10 +115,83 @@ pub struct Time {
     pub fps_avg: f64,
     pub minutes: u8,
     pub seconds: u8,
+    // number of MainLoop::update calls so far.
+    pub update_ticks: u64,
+    // progress towards the next fixed update in [0, 1), for interpolating draws.
+    // always 1.0 in UpdateMode::Variable.
+    pub update_alpha: f64,
 }
 
 pub(crate) fn register_resources(world: &mut World) {
     world.insert(InputQueue::default());
     world.insert(Core::default());
     world.insert(Time::default());
-}
\ No newline at end of file
+    world.insert(KeyBindings::default());
+    world.insert(InputBuffer::default());
+    world.insert(ComboMatcher::default());
+    world.insert(ComboQueue::default());
+    world.insert(Camera::default());
+    world.insert(AudioListener::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(downed: &[Keys]) -> InputQueue {
+        InputQueue { keys_downed: downed.to_vec(), ..InputQueue::default() }
+    }
+
+    #[test]
+    fn repeats_after_the_delay_then_every_interval() {
+        let mut queue = queue(&[Keys::Up]);
+        queue.update_holds(&[Keys::Up], 1000);
+        assert_eq!(queue.keys_repeated, vec![Keys::Up]);
+
+        queue.update_holds(&[], 1399);
+        assert_eq!(queue.keys_repeated.len(), 1);
+        queue.update_holds(&[], 1400);
+        assert_eq!(queue.keys_repeated.len(), 2);
+        queue.update_holds(&[], 1450);
+        assert_eq!(queue.keys_repeated.len(), 2);
+        queue.update_holds(&[], 1500);
+        assert_eq!(queue.keys_repeated.len(), 3);
+        // a long frame repeats only once.
+        queue.update_holds(&[], 1850);
+        assert_eq!(queue.keys_repeated.len(), 4);
+
+        assert_eq!(queue.hold_frames(Keys::Up), 6);
+        assert_eq!(queue.hold_ms(Keys::Up), 850);
+    }
+
+    #[test]
+    fn long_press_fires_once_per_hold() {
+        let mut queue = queue(&[Keys::A]);
+        queue.update_holds(&[Keys::A], 0);
+        queue.update_holds(&[], 999);
+        assert!(queue.keys_long_pressed.is_empty());
+        queue.update_holds(&[], 1000);
+        queue.update_holds(&[], 2000);
+        assert_eq!(queue.keys_long_pressed, vec![Keys::A]);
+
+        queue.keys_downed.clear();
+        queue.update_holds(&[], 2100);
+        assert_eq!(queue.hold_frames(Keys::A), 0);
+        queue.keys_downed.push(Keys::A);
+        queue.update_holds(&[Keys::A], 2200);
+        queue.update_holds(&[], 3200);
+        assert_eq!(queue.keys_long_pressed, vec![Keys::A, Keys::A]);
+    }
+
+    #[test]
+    fn key_pressed_and_released_in_one_frame() {
+        let mut queue = queue(&[]);
+        queue.update_holds(&[Keys::B], 0);
+        assert_eq!(queue.keys_repeated, vec![Keys::B]);
+        assert_eq!(queue.hold_frames(Keys::B), 0);
+
+        queue.update_holds(&[], 1000);
+        assert_eq!(queue.keys_repeated, vec![Keys::B]);
+        assert!(queue.keys_long_pressed.is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+use sdl2::pixels::Color;
+use sdl2::render::BlendMode;
+use specs::{World, WorldExt};
+use crate::{AudioContext, ImageResource, MainLoop, RenderContext, SoundResource, UpdateMode};
+use crate::combo::ComboQueue;
+use crate::resources::{Core, InputQueue, Time};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Transition {
+    #[default]
+    Cut,
+    // fades the screen out to the color and back in over duration_ms each way.
+    Fade {
+        duration_ms: u32,
+        color: Color,
+    },
+}
+
+impl Transition {
+    pub fn fade(duration_ms: u32) -> Self {
+        Transition::Fade {
+            duration_ms,
+            color: Color::RGB(0, 0, 0),
+        }
+    }
+}
+
+pub enum SceneCommand<'a, IR: ImageResource, SR: SoundResource<'a>> {
+    None,
+    Push(Box<dyn Scene<'a, IR, SR>>, Transition),
+    Pop(Transition),
+    Replace(Box<dyn Scene<'a, IR, SR>>, Transition),
+}
+
+impl<'a, IR: ImageResource, SR: SoundResource<'a>> SceneCommand<'a, IR, SR> {
+    fn transition(&self) -> Transition {
+        match self {
+            SceneCommand::None => Transition::Cut,
+            SceneCommand::Push(_, transition) => *transition,
+            SceneCommand::Pop(transition) => *transition,
+            SceneCommand::Replace(_, transition) => *transition,
+        }
+    }
+}
+
+// scenes must not call RenderContext::present, the SceneManager presents
+// once every scene and the transition overlay are drawn.
+pub trait Scene<'a, IR: ImageResource, SR: SoundResource<'a>> {
+    fn setup(&mut self, _world: &mut World, _render_context: &mut RenderContext<IR>, _audio_context: &mut AudioContext<'a, SR>) {
+        // empty
+    }
+
+    fn teardown(&mut self, _world: &mut World, _render_context: &mut RenderContext<IR>, _audio_context: &mut AudioContext<'a, SR>) {
+        // empty
+    }
+
+    fn reset_game(&mut self, _world: &mut World, _render_context: &mut RenderContext<IR>, _audio_context: &mut AudioContext<'a, SR>) {
+        // empty
+    }
+
+    fn update(&mut self, world: &mut World, audio_context: &mut AudioContext<'a, SR>) -> SceneCommand<'a, IR, SR>;
+    fn draw(&mut self, world: &mut World, render_context: &mut RenderContext<IR>);
+
+    // overlay scenes (e.g. pause menus) are drawn on top of the scene below them.
+    fn is_overlay(&self) -> bool {
+        false
+    }
+}
+
+enum FadePhase {
+    Idle,
+    Out { start_ticks: u32, duration_ms: u32, color: Color },
+    In { start_ticks: u32, duration_ms: u32, color: Color },
+}
+
+struct SceneStack<'a, IR: ImageResource, SR: SoundResource<'a>> {
+    scenes: Vec<Box<dyn Scene<'a, IR, SR>>>,
+    pending: Option<SceneCommand<'a, IR, SR>>,
+    fade: FadePhase,
+}
+
+pub struct SceneManager<'a, IR: ImageResource, SR: SoundResource<'a>> {
+    stack: RefCell<SceneStack<'a, IR, SR>>,
+    update_mode: UpdateMode,
+}
+
+impl<'a, IR: ImageResource, SR: SoundResource<'a>> SceneManager<'a, IR, SR> {
+    pub fn new(initial_scene: Box<dyn Scene<'a, IR, SR>>) -> Self {
+        SceneManager {
+            stack: RefCell::new(SceneStack {
+                scenes: Vec::new(),
+                pending: Some(SceneCommand::Push(initial_scene, Transition::Cut)),
+                fade: FadePhase::Idle,
+            }),
+            update_mode: UpdateMode::Variable,
+        }
+    }
+
+    pub fn with_update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
+}
+
+impl<'a, IR: ImageResource, SR: SoundResource<'a>> SceneStack<'a, IR, SR> {
+    fn apply(&mut self, command: SceneCommand<'a, IR, SR>, world: &mut World, render_context: &mut RenderContext<IR>, audio_context: &mut AudioContext<'a, SR>) {
+        match command {
+            SceneCommand::None => {},
+            SceneCommand::Push(mut scene, _) => {
+                scene.setup(world, render_context, audio_context);
+                self.scenes.push(scene);
+            },
+            SceneCommand::Pop(_) => {
+                if let Some(mut scene) = self.scenes.pop() {
+                    scene.teardown(world, render_context, audio_context);
+                }
+            },
+            SceneCommand::Replace(mut scene, _) => {
+                if let Some(mut old_scene) = self.scenes.pop() {
+                    old_scene.teardown(world, render_context, audio_context);
+                }
+                scene.setup(world, render_context, audio_context);
+                self.scenes.push(scene);
+            },
+        }
+
+        // keys pressed for the old scene, e.g. during a fade out, are not
+        // meant for the new one.
+        discard_input(world);
+
+        if self.scenes.is_empty() {
+            world.write_resource::<Core>().quit = true;
+        }
+    }
+
+    fn fade_alpha(&self, ticks: u32) -> Option<(u8, Color)> {
+        let (start_ticks, duration_ms, color, fading_out) = match self.fade {
+            FadePhase::Idle => return None,
+            FadePhase::Out { start_ticks, duration_ms, color } => (start_ticks, duration_ms, color, true),
+            FadePhase::In { start_ticks, duration_ms, color } => (start_ticks, duration_ms, color, false),
+        };
+        let progress = (ticks.saturating_sub(start_ticks) as f32 / duration_ms.max(1) as f32).min(1.0);
+        let alpha = if fading_out { progress } else { 1.0 - progress };
+        Some(((alpha * 255.0) as u8, color))
+    }
+}
+
+fn discard_input(world: &mut World) {
+    world.write_resource::<InputQueue>().clear();
+    world.write_resource::<ComboQueue>().combos.clear();
+}
+
+impl<'a, IR: ImageResource, SR: SoundResource<'a>> MainLoop<'a, IR, SR> for SceneManager<'a, IR, SR> {
+    fn create_world(&self) -> World {
+        World::new()
+    }
+
+    fn post_create_world(&self, _world: &mut World) {
+        // empty
+    }
+
+    fn setup(&self, _render_context: &mut RenderContext<IR>, _audio_context: &mut AudioContext<'a, SR>) {
+        // the initial scene is set up on the first frame, once the world is available.
+    }
+
+    fn update(&self, world: &mut World, audio_context: &mut AudioContext<'a, SR>) {
+        let mut stack = self.stack.borrow_mut();
+        if stack.pending.is_some() || !matches!(stack.fade, FadePhase::Idle) {
+            // no scene sees the keys pressed during a transition.
+            discard_input(world);
+            return;
+        }
+
+        let command = match stack.scenes.last_mut() {
+            Some(scene) => scene.update(world, audio_context),
+            None => return,
+        };
+
+        if !matches!(command, SceneCommand::None) {
+            stack.pending = Some(command);
+        }
+    }
+
+    fn reset_game(&self, world: &mut World, render_context: &mut RenderContext<IR>, audio_context: &mut AudioContext<'a, SR>) {
+        let mut stack = self.stack.borrow_mut();
+        if let Some(scene) = stack.scenes.last_mut() {
+            scene.reset_game(world, render_context, audio_context);
+        }
+    }
+
+    fn reset_frame(&self, world: &mut World, render_context: &mut RenderContext<IR>, audio_context: &mut AudioContext<'a, SR>) {
+        let mut stack = self.stack.borrow_mut();
+        let ticks = world.read_resource::<Time>().last_ticks;
+
+        match stack.fade {
+            FadePhase::Idle => {
+                let command = match stack.pending.take() {
+                    Some(command) => command,
+                    None => return,
+                };
+                match command.transition() {
+                    Transition::Cut => stack.apply(command, world, render_context, audio_context),
+                    Transition::Fade { duration_ms, color } => {
+                        stack.pending = Some(command);
+                        stack.fade = FadePhase::Out { start_ticks: ticks, duration_ms, color };
+                    },
+                }
+            },
+            FadePhase::Out { start_ticks, duration_ms, color } => {
+                if ticks.saturating_sub(start_ticks) >= duration_ms {
+                    if let Some(command) = stack.pending.take() {
+                        stack.apply(command, world, render_context, audio_context);
+                    }
+                    stack.fade = FadePhase::In { start_ticks: ticks, duration_ms, color };
+                }
+            },
+            FadePhase::In { start_ticks, duration_ms, .. } => {
+                if ticks.saturating_sub(start_ticks) >= duration_ms {
+                    stack.fade = FadePhase::Idle;
+                }
+            },
+        }
+    }
+
+    fn draw(&self, world: &mut World, render_context: &mut RenderContext<IR>) {
+        let mut stack = self.stack.borrow_mut();
+
+        let first = stack.scenes
+            .iter()
+            .rposition(|scene| !scene.is_overlay())
+            .unwrap_or(0);
+        for scene in stack.scenes.iter_mut().skip(first) {
+            scene.draw(world, render_context);
+        }
+
+        let ticks = world.read_resource::<Time>().last_ticks;
+        if let Some((alpha, color)) = stack.fade_alpha(ticks) {
+            render_context.canvas.set_blend_mode(BlendMode::Blend);
+            render_context.clear(Color::RGBA(color.r, color.g, color.b, alpha));
+            render_context.canvas.set_blend_mode(BlendMode::None);
+        }
+
+        render_context.present();
+    }
+
+    fn update_mode(&self) -> UpdateMode {
+        self.update_mode
+    }
+
+    // tears down the scenes left on the stack, top first.
+    fn teardown(&self, world: &mut World, render_context: &mut RenderContext<IR>, audio_context: &mut AudioContext<'a, SR>) {
+        let mut stack = self.stack.borrow_mut();
+        stack.pending = None;
+        while let Some(mut scene) = stack.scenes.pop() {
+            scene.teardown(world, render_context, audio_context);
+        }
+    }
+}
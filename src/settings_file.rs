@@ -0,0 +1,20 @@
+use crate::error::{Error, Result};
+
+// reads the `Name = value` lines of a settings file such as KeyBindings or
+// VolumeSettings, skipping blank lines and `#` comments. setting gets the
+// trimmed name and value, and its error is reported with the line number.
+pub(crate) fn parse_settings(
+    text: &str,
+    mut setting: impl FnMut(&str, &str) -> std::result::Result<(), &'static str>) -> Result<()> {
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = |message: &str| Error::Config(format!("line {}: {}", number + 1, message));
+        let (name, value) = line.split_once('=').ok_or_else(|| invalid("expected `Name = value`"))?;
+        setting(name.trim(), value.trim()).map_err(invalid)?;
+    }
+    Ok(())
+}
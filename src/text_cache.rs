@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use sdl2::pixels::Color;
+use sdl2::render::Texture;
+use crate::font::TextStyle;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct TextKey<Id> {
+    text: String,
+    font: Option<Id>,
+    scale: u32,
+    color: Color,
+    alpha: u8,
+    shadow_offset: (i32, i32),
+    shadow_color: Option<Color>,
+    outline_width: u32,
+    outline_color: Option<Color>,
+}
+
+impl<Id: Clone> TextKey<Id> {
+    pub(crate) fn new(text: &str, style: &TextStyle<Id>) -> Self {
+        TextKey {
+            text: text.to_string(),
+            font: style.font.clone(),
+            scale: style.scale.to_bits(),
+            color: style.color,
+            alpha: style.alpha,
+            shadow_offset: style.shadow_offset,
+            shadow_color: style.shadow_color,
+            outline_width: style.outline_width,
+            outline_color: style.outline_color,
+        }
+    }
+}
+
+struct CachedText {
+    texture: Texture,
+    size: (u32, u32),
+    last_used: u64,
+}
+
+// rendered text textures, keyed by string and style. the least recently
+// drawn one is dropped when the cache is full.
+pub struct TextCache<Id> {
+    entries: HashMap<TextKey<Id>, CachedText>,
+    capacity: usize,
+    clock: u64,
+}
+
+impl<Id: Eq + Hash + Clone> TextCache<Id> {
+    pub fn new(capacity: usize) -> Self {
+        TextCache {
+            entries: HashMap::new(),
+            capacity,
+            clock: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > self.capacity {
+            self.evict();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        for (_, entry) in self.entries.drain() {
+            destroy_texture(entry.texture);
+        }
+    }
+
+    pub(crate) fn contains(&self, key: &TextKey<Id>) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub(crate) fn get(&mut self, key: &TextKey<Id>) -> Option<(&Texture, (u32, u32))> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.clock;
+        Some((&entry.texture, entry.size))
+    }
+
+    // with a capacity of 0 the texture is kept until the next insert, so the
+    // text can still be drawn once.
+    pub(crate) fn insert(&mut self, key: TextKey<Id>, texture: Texture, size: (u32, u32)) {
+        while !self.entries.is_empty() && self.entries.len() >= self.capacity {
+            self.evict();
+        }
+        self.clock += 1;
+        if let Some(previous) = self.entries.insert(key, CachedText { texture, size, last_used: self.clock }) {
+            destroy_texture(previous.texture);
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = self.entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(entry) = oldest.and_then(|key| self.entries.remove(&key)) {
+            destroy_texture(entry.texture);
+        }
+    }
+}
+
+// with unsafe_textures, dropping a Texture leaks it until the renderer is
+// destroyed, so replaced textures are freed by hand.
+pub(crate) fn destroy_texture(texture: Texture) {
+    // the renderer is alive for as long as the RenderContext holding the texture.
+    unsafe { texture.destroy() };
+}
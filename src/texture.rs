@@ -1,53 +1,182 @@
 extern crate sdl2;
 
 use std::collections::HashMap;
+use std::hash::Hash;
 use imagesize::blob_size;
 use sdl2::image::{InitFlag, LoadTexture};
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
-use sdl2::render::{Texture, WindowCanvas};
+use sdl2::render::{BlendMode, Texture, WindowCanvas};
 use sdl2::Sdl;
-use crate::constants::{SCREEN_HEIGHT, SCREEN_WIDTH, WINDOW_TITLE};
+use crate::atlas::Atlas;
+use crate::components::Transform;
+use crate::config::GameConfig;
+use crate::error::{Error, Result};
+use crate::font::{Font, TextAlign, TextLayout, TextStyle};
+use crate::text_cache::{destroy_texture, TextCache, TextKey};
 use crate::ImageResource;
 
-fn draw_text_to_canvas(
-    canvas: &mut WindowCanvas, texture: &Texture, text: String, x: i32, y: i32, font_height: u32, scale: f32) {
-    for (i, char) in text.chars().collect::<Vec<char>>().iter().enumerate() {
-        let index = u32::from(*char) - 0x20;
-        let dst_height = if scale == 1.0 { font_height } else { (font_height as f32 * scale) as u32 };
-        let src = Rect::new(
-            (font_height * index) as i32, 0, font_height, font_height);
+fn draw_line_to_canvas(
+    canvas: &mut WindowCanvas, pages: &[&Texture], font: &Font, line: &str, x: i32, y: i32, scale: f32) -> Result<()> {
+    for (pen, glyph) in font.layout(line) {
+        if glyph.width == 0 || glyph.height == 0 {
+            continue;
+        }
+        let texture = pages.get(glyph.page as usize).ok_or(Error::TextureNotFound)?;
+        let src = Rect::new(glyph.x, glyph.y, glyph.width, glyph.height);
         let dst = Rect::new(
-            x + (dst_height * (i as u32)) as i32, y, dst_height.clone(), dst_height.clone());
-        canvas.copy(texture, src, dst).unwrap();
+            x + scaled(pen + glyph.offset_x, scale),
+            y + scaled(glyph.offset_y, scale),
+            scaled(glyph.width as i32, scale) as u32,
+            scaled(glyph.height as i32, scale) as u32);
+        canvas.copy(texture, src, dst).map_err(Error::Render)?;
+    }
+    Ok(())
+}
+
+// the page textures of a font, borrowed from the texture map.
+struct FontPages<'t, Id> {
+    textures: &'t mut HashMap<Id, Texture>,
+    ids: &'t [Id],
+}
+
+impl<Id: Eq + Hash> FontPages<'_, Id> {
+    fn tint(&mut self, color: Color) {
+        for id in self.ids {
+            if let Some(texture) = self.textures.get_mut(id) {
+                texture.set_color_mod(color.r, color.g, color.b);
+                texture.set_alpha_mod(color.a);
+            }
+        }
+    }
+
+    fn textures(&self) -> Result<Vec<&Texture>> {
+        self.ids
+            .iter()
+            .map(|id| self.textures.get(id).ok_or(Error::TextureNotFound))
+            .collect()
+    }
+}
+
+// draws the line once per pass of the style, tinting the font pages for each.
+fn draw_styled_line<Id: Eq + Hash>(
+    canvas: &mut WindowCanvas, pages: &mut FontPages<Id>, font: &Font, line: &str, x: i32, y: i32, style: &TextStyle<Id>) -> Result<()> {
+    let mut result = Ok(());
+    for (offset_x, offset_y, color) in style.passes() {
+        pages.tint(color);
+        result = pages
+            .textures()
+            .and_then(|textures| draw_line_to_canvas(canvas, &textures, font, line, x + offset_x, y + offset_y, style.scale));
+        if result.is_err() {
+            break;
+        }
     }
+    pages.tint(Color::WHITE);
+    result
+}
+
+fn draw_text_to_canvas<Id: Eq + Hash>(
+    canvas: &mut WindowCanvas, pages: &mut FontPages<Id>, font: &Font, text: &str, x: i32, y: i32, style: &TextStyle<Id>) -> Result<()> {
+    let line_advance = scaled(font.line_height as i32, style.scale);
+    for (i, line) in font.wrap_lines(text, None).iter().enumerate() {
+        draw_styled_line(canvas, pages, font, line, x, y + line_advance * i as i32, style)?;
+    }
+    Ok(())
+}
+
+// renders text into a new transparent texture sized to fit it, shadow and
+// outline included.
+fn render_text_texture<Id: Eq + Hash>(
+    canvas: &mut WindowCanvas, pages: &mut FontPages<Id>, font: &Font, text: &str, style: &TextStyle<Id>) -> Result<(Texture, (u32, u32))> {
+    let (width, height) = font.measure(text, None, &TextLayout::default());
+    let (left, top, right, bottom) = style.padding();
+    let width = (scaled(width as i32, style.scale) as u32 + left + right).max(1);
+    let height = (scaled(height as i32, style.scale) as u32 + top + bottom).max(1);
+
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator.create_texture_target(PixelFormatEnum::ARGB8888, width, height)?;
+    texture.set_blend_mode(BlendMode::Blend);
+    let mut result = Ok(());
+    canvas.with_texture_canvas(&mut texture, |texture_canvas| {
+        texture_canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+        texture_canvas.clear();
+        result = draw_text_to_canvas(texture_canvas, pages, font, text, left as i32, top as i32, style);
+    })?;
+    result?;
+    Ok((texture, (width, height)))
+}
+
+fn scaled(value: i32, scale: f32) -> i32 {
+    if scale == 1.0 { value } else { (value as f32 * scale) as i32 }
+}
+
+fn load_font<IR: ImageResource>(resource: &IR, id: &IR::TextureId, texture_sizes: &HashMap<IR::TextureId, (u32, u32)>) -> Result<Font> {
+    if let Some(descriptor) = resource.get_font_descriptor(id) {
+        return Font::from_fnt(descriptor);
+    }
+    match resource.get_glyph_map(id) {
+        Some(glyph_map) => Ok(Font::from_glyph_map(&glyph_map)),
+        None => {
+            let (image_width, _) = texture_sizes.get(id).ok_or(Error::TextureNotFound)?;
+            Ok(Font::monospace(*resource.get_font_height(id), *image_width))
+        },
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum RenderMode {
+    #[default]
+    Window,
+    // renders into the in-memory framebuffer of SDL's dummy video driver,
+    // so no display is needed. use RenderContext::read_pixels to inspect frames.
+    Headless,
 }
 
 pub struct RenderContext<IR: ImageResource> {
     pub canvas: WindowCanvas,
     pub textures: HashMap<IR::TextureId, Texture>,
     pub texture_sizes: HashMap<IR::TextureId, (u32, u32)>,
+    pub atlases: HashMap<IR::TextureId, Atlas>,
+    // fonts are loaded the first time they are drawn with.
+    pub fonts: HashMap<IR::TextureId, Font>,
+    // textures of text drawn with draw_text_cached.
+    pub text_cache: TextCache<IR::TextureId>,
     pub resource: IR,
+    pub mode: RenderMode,
+    pub screen_width: u32,
+    pub screen_height: u32,
+    // camera offset applied to world-space draws by RenderingHelper, set every frame.
+    pub view_offset: (i32, i32),
 }
 
 impl<IR: ImageResource> RenderContext<IR> {
-    pub fn new(sdl_context: &Sdl, resource: IR) -> Self {
-        let video_subsystem = sdl_context.video().unwrap();
-        let _image_context = sdl2::image::init(InitFlag::PNG | InitFlag::JPG).unwrap();
-
-        let window = video_subsystem.window(
-            WINDOW_TITLE,
-            SCREEN_WIDTH,
-            SCREEN_HEIGHT)
-            .position_centered()
-            .build()
-            .unwrap();
+    pub fn new(sdl_context: &Sdl, resource: IR, config: &GameConfig) -> Result<Self> {
+        let video_subsystem = sdl_context.video().map_err(Error::Sdl)?;
+        let _image_context = sdl2::image::init(InitFlag::PNG | InitFlag::JPG).map_err(Error::Sdl)?;
+
+        let mode = config.render_mode;
+        let (screen_width, screen_height) = config.get_screen_size();
+        let (window_width, window_height) = config.get_window_size();
+
+        let mut window_builder = video_subsystem.window(
+            &config.title,
+            window_width,
+            window_height);
+        match mode {
+            RenderMode::Window => window_builder.position_centered(),
+            RenderMode::Headless => window_builder.hidden(),
+        };
+        let window = window_builder
+            .build()?;
 
         let mut canvas = window
             .into_canvas()
             .software()
-            .build()
-            .unwrap();
+            .build()?;
+
+        if (window_width, window_height) != (screen_width, screen_height) {
+            canvas.set_logical_size(screen_width, screen_height)?;
+        }
 
         let texture_creator = canvas.texture_creator();
 
@@ -55,7 +184,7 @@ impl<IR: ImageResource> RenderContext<IR> {
             let mut m = HashMap::new();
             for texture_id in resource.get_image_ids() {
                 let raw = resource.get_image(&texture_id);
-                let texture = texture_creator.load_texture_bytes(raw).unwrap();
+                let texture = texture_creator.load_texture_bytes(raw).map_err(Error::Image)?;
                 m.insert(texture_id, texture);
             }
             m
@@ -65,82 +194,327 @@ impl<IR: ImageResource> RenderContext<IR> {
             let mut m = HashMap::new();
             for texture_id in resource.get_image_ids() {
                 let raw = resource.get_image(&texture_id);
-                let size = blob_size(raw).unwrap();
+                let size = blob_size(raw)?;
                 m.insert(texture_id, (size.width as u32, size.height as u32));
             }
             m
         };
 
+        let atlases = {
+            let mut m = HashMap::new();
+            for texture_id in resource.get_image_ids() {
+                let frames = resource.get_atlas_frames(&texture_id);
+                if !frames.is_empty() {
+                    m.insert(texture_id, Atlas::new(frames));
+                }
+            }
+            m
+        };
+
         let default_font_id = resource.get_default_font_id();
-        let font_height = resource.get_font_height(&default_font_id);
+        let mut fonts = HashMap::new();
+        fonts.insert(default_font_id.clone(), load_font(&resource, &default_font_id, &texture_sizes)?);
 
         for texture_id in resource.get_text_ids() {
             let text = resource.get_text(&texture_id);
-            let width = font_height.clone() * (text.len() as u32);
-            let height = font_height.clone();
-            let mut texture = texture_creator
-                .create_texture_target(
-                    texture_creator.default_pixel_format(), width, height)
-                .unwrap();
-            let _ = canvas.with_texture_canvas(&mut texture, |texture_canvas| {
-                let font_texture = textures.get(&default_font_id).unwrap();
-                draw_text_to_canvas(texture_canvas, font_texture, text.clone(), 0, 0, font_height.clone(), 1.0);
-            });
-            texture_sizes.insert(texture_id.clone(), (width, height));
+            let style = resource.get_text_style(&texture_id);
+            let font_id = style.font.clone().unwrap_or_else(|| default_font_id.clone());
+            if !fonts.contains_key(&font_id) {
+                fonts.insert(font_id.clone(), load_font(&resource, &font_id, &texture_sizes)?);
+            }
+            let page_ids = resource.get_font_pages(&font_id);
+            let mut pages = FontPages { textures: &mut textures, ids: &page_ids };
+            let (texture, size) = render_text_texture(&mut canvas, &mut pages, &fonts[&font_id], text, &style)?;
+            texture_sizes.insert(texture_id.clone(), size);
             textures.insert(texture_id.clone(), texture);
         }
 
-        RenderContext {
+        Ok(RenderContext {
             canvas,
             textures,
             texture_sizes,
+            atlases,
+            fonts,
+            text_cache: TextCache::new(config.text_cache_size),
             resource,
-        }
+            mode,
+            screen_width,
+            screen_height,
+            view_offset: (0, 0),
+        })
     }
 
     pub fn present(&mut self) {
         self.canvas.present();
     }
 
+    pub fn is_headless(&self) -> bool {
+        self.mode == RenderMode::Headless
+    }
+
+    // returns the current frame as tightly packed RGBA bytes, row by row.
+    pub fn read_pixels(&self) -> Result<Vec<u8>> {
+        self.canvas
+            .read_pixels(None, PixelFormatEnum::RGBA32)
+            .map_err(Error::Render)
+    }
+
+    pub fn read_pixel(&self, x: i32, y: i32) -> Result<Color> {
+        let pixel = self.canvas
+            .read_pixels(Rect::new(x, y, 1, 1), PixelFormatEnum::RGBA32)
+            .map_err(Error::Render)?;
+        Ok(Color::RGBA(pixel[0], pixel[1], pixel[2], pixel[3]))
+    }
+
+    // the draw functions below ignore errors so a frame with a missing
+    // texture still renders. use the try_ variants to handle them.
+
     pub fn clear(&mut self, color: Color) {
+        let _ = self.try_clear(color);
+    }
+
+    pub fn try_clear(&mut self, color: Color) -> Result<()> {
         self.canvas.set_draw_color(color);
-        self.canvas.fill_rect(Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT)).unwrap();
+        self.canvas
+            .fill_rect(Rect::new(0, 0, self.screen_width, self.screen_height))
+            .map_err(Error::Render)
     }
 
     pub fn draw_bg(&mut self, id: IR::TextureId) {
-        let texture = self.textures.get(&id).unwrap();
+        let _ = self.try_draw_bg(id);
+    }
+
+    pub fn try_draw_bg(&mut self, id: IR::TextureId) -> Result<()> {
+        let texture = self.textures.get(&id).ok_or(Error::TextureNotFound)?;
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.copy(texture, None, None).unwrap();
+        self.canvas.copy(texture, None, None).map_err(Error::Render)
     }
 
     pub fn draw(&mut self, id: IR::TextureId, x: i32, y: i32) {
-        let texture = self.textures.get(&id).unwrap();
-        let size = self.texture_sizes.get(&id).unwrap();
+        let _ = self.try_draw(id, x, y);
+    }
+
+    pub fn try_draw(&mut self, id: IR::TextureId, x: i32, y: i32) -> Result<()> {
+        let texture = self.textures.get(&id).ok_or(Error::TextureNotFound)?;
+        let size = self.texture_sizes.get(&id).ok_or(Error::TextureNotFound)?;
         let area = Rect::new(x, y, size.0, size.1);
-        self.canvas.copy(texture, None, area).unwrap();
+        self.canvas.copy(texture, None, area).map_err(Error::Render)
+    }
+
+    pub fn frame_index(&self, id: &IR::TextureId, name: &str) -> Option<usize> {
+        self.atlases.get(id).and_then(|atlas| atlas.index_of(name))
+    }
+
+    pub fn frame_size(&self, id: &IR::TextureId, frame: usize) -> Option<(u32, u32)> {
+        let frame = self.atlases.get(id)?.frame(frame)?;
+        Some((frame.width, frame.height))
+    }
+
+    pub fn draw_frame(&mut self, id: IR::TextureId, frame: usize, x: i32, y: i32) {
+        let _ = self.try_draw_frame(id, frame, x, y);
+    }
+
+    pub fn try_draw_frame(&mut self, id: IR::TextureId, frame: usize, x: i32, y: i32) -> Result<()> {
+        let texture = self.textures.get(&id).ok_or(Error::TextureNotFound)?;
+        let frame = self.atlases
+            .get(&id)
+            .and_then(|atlas| atlas.frame(frame))
+            .ok_or(Error::FrameNotFound)?;
+        let area = Rect::new(x, y, frame.width, frame.height);
+        self.canvas.copy(texture, frame.rect(), area).map_err(Error::Render)
+    }
+
+    pub fn draw_frame_named(&mut self, id: IR::TextureId, name: &str, x: i32, y: i32) {
+        if let Some(frame) = self.frame_index(&id, name) {
+            self.draw_frame(id, frame, x, y);
+        }
+    }
+
+    pub fn draw_ex(&mut self, id: IR::TextureId, frame: Option<usize>, transform: &Transform) {
+        let _ = self.try_draw_ex(id, frame, transform);
+    }
+
+    // draws the whole image, or one atlas frame, scaled, rotated and flipped.
+    pub fn try_draw_ex(&mut self, id: IR::TextureId, frame: Option<usize>, transform: &Transform) -> Result<()> {
+        let texture = self.textures.get(&id).ok_or(Error::TextureNotFound)?;
+        let (src, width, height) = match frame {
+            Some(frame) => {
+                let frame = self.atlases
+                    .get(&id)
+                    .and_then(|atlas| atlas.frame(frame))
+                    .ok_or(Error::FrameNotFound)?;
+                (Some(frame.rect()), frame.width, frame.height)
+            },
+            None => {
+                let size = self.texture_sizes.get(&id).ok_or(Error::TextureNotFound)?;
+                (None, size.0, size.1)
+            },
+        };
+        let dst = Rect::new(
+            transform.x.round() as i32,
+            transform.y.round() as i32,
+            (width as f32 * transform.scale_x.abs()).round() as u32,
+            (height as f32 * transform.scale_y.abs()).round() as u32);
+        self.canvas
+            .copy_ex(texture, src, dst, transform.rotation, None, transform.flip_horizontal, transform.flip_vertical)
+            .map_err(Error::Render)
     }
 
     pub fn draw_text(&mut self, text: String, x: i32, y: i32) {
         let default_font_id = self.resource.get_default_font_id();
-        let height = self.resource.get_font_height(&default_font_id);
-        let texture = self.textures.get(&default_font_id).unwrap();
-        draw_text_to_canvas(&mut self.canvas, texture, text, x, y, height.clone(), 1.0);
+        let _ = self.try_draw_text(text, x, y, default_font_id, 1.0);
     }
 
     pub fn draw_text_font(&mut self, text: String, x: i32, y: i32, font: IR::TextureId) {
-        let height = self.resource.get_font_height(&font);
-        let texture = self.textures.get(&font).unwrap();
-        draw_text_to_canvas(&mut self.canvas, texture, text, x, y, height.clone(), 1.0);
+        let _ = self.try_draw_text(text, x, y, font, 1.0);
     }
 
     pub fn draw_text_scale(&mut self, text: String, x: i32, y: i32, scale: f32) {
         let default_font_id = self.resource.get_default_font_id();
-        let height = self.resource.get_font_height(&default_font_id);
-        let texture = self.textures.get(&default_font_id).unwrap();
-        draw_text_to_canvas(&mut self.canvas, texture, text, x, y, height.clone(), scale);
+        let _ = self.try_draw_text(text, x, y, default_font_id, scale);
+    }
+
+    pub fn try_draw_text(&mut self, text: String, x: i32, y: i32, font: IR::TextureId, scale: f32) -> Result<()> {
+        self.try_draw_text_styled(&text, x, y, &TextStyle::new().font(font).scale(scale))
+    }
+
+    pub fn draw_text_styled(&mut self, text: &str, x: i32, y: i32, style: &TextStyle<IR::TextureId>) {
+        let _ = self.try_draw_text_styled(text, x, y, style);
+    }
+
+    pub fn try_draw_text_styled(&mut self, text: &str, x: i32, y: i32, style: &TextStyle<IR::TextureId>) -> Result<()> {
+        let font = self.style_font(style)?;
+        let page_ids = self.resource.get_font_pages(&font);
+        let mut pages = FontPages { textures: &mut self.textures, ids: &page_ids };
+        draw_text_to_canvas(&mut self.canvas, &mut pages, &self.fonts[&font], text, x, y, style)
+    }
+
+    // draws text from a texture rendered the first time the string is drawn
+    // with this style, for text that changes now and then like a score.
+    pub fn draw_text_cached(&mut self, text: &str, x: i32, y: i32, style: &TextStyle<IR::TextureId>) {
+        let _ = self.try_draw_text_cached(text, x, y, style);
+    }
+
+    pub fn try_draw_text_cached(&mut self, text: &str, x: i32, y: i32, style: &TextStyle<IR::TextureId>) -> Result<()> {
+        let key = TextKey::new(text, style);
+        if !self.text_cache.contains(&key) {
+            let (texture, size) = self.render_text(text, style)?;
+            self.text_cache.insert(key.clone(), texture, size);
+        }
+        let (texture, (width, height)) = self.text_cache.get(&key).ok_or(Error::TextureNotFound)?;
+        let (left, top, _, _) = style.padding();
+        let area = Rect::new(x - left as i32, y - top as i32, width, height);
+        self.canvas.copy(texture, None, area).map_err(Error::Render)
+    }
+
+    // renders text into the texture id, replacing what it held, so it can be
+    // drawn with draw like the texts from get_text_ids.
+    pub fn set_text(&mut self, id: IR::TextureId, text: &str, style: &TextStyle<IR::TextureId>) {
+        let _ = self.try_set_text(id, text, style);
+    }
+
+    pub fn try_set_text(&mut self, id: IR::TextureId, text: &str, style: &TextStyle<IR::TextureId>) -> Result<()> {
+        let (texture, size) = self.render_text(text, style)?;
+        if let Some(previous) = self.textures.insert(id.clone(), texture) {
+            destroy_texture(previous);
+        }
+        self.texture_sizes.insert(id, size);
+        Ok(())
+    }
+
+    pub fn font(&mut self, id: &IR::TextureId) -> Result<&Font> {
+        self.prepare_font(id)?;
+        Ok(&self.fonts[id])
+    }
+
+    // draws text inside area, wrapped and aligned by layout. anything outside
+    // the area is clipped.
+    pub fn draw_text_box(&mut self, text: &str, area: Rect, font: IR::TextureId, scale: f32, layout: &TextLayout) {
+        let _ = self.try_draw_text_box(text, area, font, scale, layout);
+    }
+
+    pub fn try_draw_text_box(&mut self, text: &str, area: Rect, font: IR::TextureId, scale: f32, layout: &TextLayout) -> Result<()> {
+        self.try_draw_text_box_styled(text, area, layout, &TextStyle::new().font(font).scale(scale))
+    }
+
+    pub fn draw_text_box_styled(&mut self, text: &str, area: Rect, layout: &TextLayout, style: &TextStyle<IR::TextureId>) {
+        let _ = self.try_draw_text_box_styled(text, area, layout, style);
+    }
+
+    pub fn try_draw_text_box_styled(&mut self, text: &str, area: Rect, layout: &TextLayout, style: &TextStyle<IR::TextureId>) -> Result<()> {
+        let font = self.style_font(style)?;
+        let page_ids = self.resource.get_font_pages(&font);
+        let mut pages = FontPages { textures: &mut self.textures, ids: &page_ids };
+        let font = &self.fonts[&font];
+        let scale = style.scale;
+
+        // the shadow and outline are kept inside the area, like measure_text counts them.
+        let (left, top, right, _) = style.padding();
+        let text_width = area.width().saturating_sub(left + right);
+        let max_width = if layout.wrap { Some((text_width as f32 / scale) as u32) } else { None };
+        let line_advance = scaled(font.line_advance(layout), scale);
+        let previous_clip = self.canvas.clip_rect();
+        self.canvas.set_clip_rect(area);
+
+        let mut result = Ok(());
+        for (i, line) in font.wrap_lines(text, max_width).iter().enumerate() {
+            let y = area.y() + top as i32 + line_advance * i as i32;
+            if y >= area.bottom() {
+                break;
+            }
+            let free_width = text_width as i32 - scaled(font.text_width(line) as i32, scale);
+            let x = area.x() + left as i32 + match layout.align {
+                TextAlign::Left => 0,
+                TextAlign::Center => free_width / 2,
+                TextAlign::Right => free_width,
+            };
+            result = draw_styled_line(&mut self.canvas, &mut pages, font, line, x, y, style);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        self.canvas.set_clip_rect(previous_clip);
+        result
+    }
+
+    // size the text takes when drawn with draw_text_box_styled into a box
+    // max_width wide, or on unbounded lines with None, including the shadow
+    // and outline.
+    pub fn measure_text(&mut self, text: &str, max_width: Option<u32>, layout: &TextLayout, style: &TextStyle<IR::TextureId>) -> Result<(u32, u32)> {
+        let font = self.style_font(style)?;
+        let scale = style.scale;
+        let max_width = max_width.map(|max_width| (max_width as f32 / scale) as u32);
+        let (width, height) = self.fonts[&font].measure(text, max_width, layout);
+        let (left, top, right, bottom) = style.padding();
+        Ok((
+            scaled(width as i32, scale) as u32 + left + right,
+            scaled(height as i32, scale) as u32 + top + bottom,
+        ))
+    }
+
+    fn render_text(&mut self, text: &str, style: &TextStyle<IR::TextureId>) -> Result<(Texture, (u32, u32))> {
+        let font = self.style_font(style)?;
+        let page_ids = self.resource.get_font_pages(&font);
+        let mut pages = FontPages { textures: &mut self.textures, ids: &page_ids };
+        render_text_texture(&mut self.canvas, &mut pages, &self.fonts[&font], text, style)
+    }
+
+    fn style_font(&mut self, style: &TextStyle<IR::TextureId>) -> Result<IR::TextureId> {
+        let font = style.font.clone().unwrap_or_else(|| self.resource.get_default_font_id());
+        self.prepare_font(&font)?;
+        Ok(font)
+    }
+
+    fn prepare_font(&mut self, id: &IR::TextureId) -> Result<()> {
+        if !self.fonts.contains_key(id) {
+            let font = load_font(&self.resource, id, &self.texture_sizes)?;
+            self.fonts.insert(id.clone(), font);
+        }
+        Ok(())
     }
 }
 
-pub(crate) fn initialize_render<IR: ImageResource>(sdl_context: &Sdl, resource: IR) -> RenderContext<IR> {
-    RenderContext::new(sdl_context, resource)
+pub(crate) fn initialize_render<IR: ImageResource>(sdl_context: &Sdl, resource: IR, config: &GameConfig) -> Result<RenderContext<IR>> {
+    RenderContext::new(sdl_context, resource, config)
 }
\ No newline at end of file
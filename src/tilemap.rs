@@ -0,0 +1,875 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use roxmltree::{Document, Node};
+use serde_json::Value;
+use specs::{Builder, Entity, World, WorldExt};
+use crate::atlas::AtlasFrame;
+use crate::components::{Position, Renderable, Transform};
+use crate::error::{Error, Result};
+use crate::texture::RenderContext;
+use crate::ImageResource;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const GID_MASK: u32 = !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY);
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    // also used for color, file and object properties.
+    String(String),
+}
+
+impl PropertyValue {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            PropertyValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            PropertyValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            PropertyValue::Float(value) => Some(*value),
+            PropertyValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            PropertyValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+pub type Properties = HashMap<String, PropertyValue>;
+
+#[derive(Clone, Debug)]
+pub struct Tileset {
+    pub first_gid: u32,
+    pub name: String,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    // path of the tileset image as written in the map file.
+    pub image: String,
+    pub image_width: u32,
+    pub image_height: u32,
+}
+
+impl Tileset {
+    // atlas frames for the tileset image, indexed by local tile id.
+    // return these from ImageResource::get_atlas_frames for the image.
+    pub fn frames(&self) -> Vec<AtlasFrame> {
+        (0..self.tile_count)
+            .map(|id| {
+                let column = id % self.columns.max(1);
+                let row = id / self.columns.max(1);
+                AtlasFrame::new(
+                    (self.margin + column * (self.tile_width + self.spacing)) as i32,
+                    (self.margin + row * (self.tile_height + self.spacing)) as i32,
+                    self.tile_width,
+                    self.tile_height)
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Tile {
+    // index into TileMap::tilesets.
+    pub tileset: usize,
+    // tile id within the tileset, which is also its atlas frame.
+    pub id: u32,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    // swaps the x and y axes before the other flips, which is how Tiled
+    // stores rotations.
+    pub flip_diagonal: bool,
+}
+
+impl Tile {
+    pub fn is_transformed(&self) -> bool {
+        self.flip_horizontal || self.flip_vertical || self.flip_diagonal
+    }
+
+    // the tile's flips as a Transform at x, y: clockwise rotation in degrees,
+    // then horizontal and vertical flip. rotations turn the tile around its
+    // center, which matches Tiled for square tiles.
+    pub fn transform(&self, x: f32, y: f32, z: u8) -> Transform {
+        let (rotation, horizontal, vertical) = match (self.flip_diagonal, self.flip_horizontal, self.flip_vertical) {
+            (false, horizontal, vertical) => (0.0, horizontal, vertical),
+            (true, false, false) => (90.0, false, true),
+            (true, true, false) => (90.0, false, false),
+            (true, false, true) => (270.0, false, false),
+            (true, true, true) => (90.0, true, false),
+        };
+        Transform::new(x, y, z).with_rotation(rotation).with_flip(horizontal, vertical)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TileLayer {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub visible: bool,
+    // global tile ids row by row, 0 for no tile. the top bits hold flip flags.
+    pub data: Vec<u32>,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub gid: Option<u32>,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+impl MapObject {
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.get(name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub visible: bool,
+    pub objects: Vec<MapObject>,
+    pub properties: Properties,
+}
+
+// group layers are flattened into their children.
+#[derive(Clone, Debug)]
+pub enum Layer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+#[derive(Clone, Debug)]
+pub struct TileMap {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub tilesets: Vec<Tileset>,
+    pub layers: Vec<Layer>,
+    pub properties: Properties,
+}
+
+fn map_error(message: impl Into<String>) -> Error {
+    Error::Map(message.into())
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for char in text.bytes().filter(|char| !char.is_ascii_whitespace() && *char != b'=') {
+        let value = match char {
+            b'A'..=b'Z' => char - b'A',
+            b'a'..=b'z' => char - b'a' + 26,
+            b'0'..=b'9' => char - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(map_error("invalid base64 tile data")),
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn decode_tile_data(encoding: &str, compression: &str, text: &str) -> Result<Vec<u32>> {
+    if !compression.is_empty() {
+        return Err(map_error(format!("unsupported tile data compression `{}`, save the map uncompressed", compression)));
+    }
+
+    match encoding {
+        "csv" => text
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse().map_err(|_| map_error("invalid csv tile data")))
+            .collect(),
+        "base64" => Ok(decode_base64(text)?
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()),
+        _ => Err(map_error(format!("unsupported tile data encoding `{}`", encoding))),
+    }
+}
+
+fn parse_property(kind: &str, value: &str) -> Result<PropertyValue> {
+    let invalid = || map_error(format!("invalid {} property `{}`", kind, value));
+    Ok(match kind {
+        "bool" => PropertyValue::Bool(value.parse().map_err(|_| invalid())?),
+        "int" => PropertyValue::Int(value.parse().map_err(|_| invalid())?),
+        "float" => PropertyValue::Float(value.parse().map_err(|_| invalid())?),
+        _ => PropertyValue::String(value.to_string()),
+    })
+}
+
+// TMX (XML) parsing
+
+fn attribute<T: std::str::FromStr>(node: &Node, name: &str) -> Result<T> {
+    node.attribute(name)
+        .ok_or_else(|| map_error(format!("<{}> is missing `{}`", node.tag_name().name(), name)))?
+        .parse()
+        .map_err(|_| map_error(format!("<{}> has an invalid `{}`", node.tag_name().name(), name)))
+}
+
+fn attribute_or<T: std::str::FromStr>(node: &Node, name: &str, default: T) -> Result<T> {
+    match node.attribute(name) {
+        Some(_) => attribute(node, name),
+        None => Ok(default),
+    }
+}
+
+fn tmx_properties(node: &Node) -> Result<Properties> {
+    let mut properties = Properties::new();
+    for property in node.children().filter(|n| n.has_tag_name("properties")).flat_map(|n| n.children()) {
+        if !property.has_tag_name("property") {
+            continue;
+        }
+        let name = property.attribute("name").unwrap_or_default().to_string();
+        let kind = property.attribute("type").unwrap_or("string");
+        // multi-line strings are stored as text instead of an attribute.
+        let value = property.attribute("value").or_else(|| property.text()).unwrap_or_default();
+        properties.insert(name, parse_property(kind, value)?);
+    }
+    Ok(properties)
+}
+
+fn tmx_tileset(node: &Node, first_gid: u32) -> Result<Tileset> {
+    let image = node
+        .children()
+        .find(|n| n.has_tag_name("image"))
+        .ok_or_else(|| map_error("tilesets without a single image are not supported"))?;
+    Ok(Tileset {
+        first_gid,
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        tile_width: attribute(node, "tilewidth")?,
+        tile_height: attribute(node, "tileheight")?,
+        tile_count: attribute(node, "tilecount")?,
+        columns: attribute(node, "columns")?,
+        spacing: attribute_or(node, "spacing", 0)?,
+        margin: attribute_or(node, "margin", 0)?,
+        image: attribute(&image, "source")?,
+        image_width: attribute_or(&image, "width", 0)?,
+        image_height: attribute_or(&image, "height", 0)?,
+    })
+}
+
+fn tmx_layers(node: &Node, layers: &mut Vec<Layer>) -> Result<()> {
+    for child in node.children().filter(|n| n.is_element()) {
+        match child.tag_name().name() {
+            "layer" => {
+                let data = child
+                    .children()
+                    .find(|n| n.has_tag_name("data"))
+                    .ok_or_else(|| map_error("infinite maps are not supported"))?;
+                if data.children().any(|n| n.has_tag_name("chunk")) {
+                    return Err(map_error("infinite maps are not supported"));
+                }
+                layers.push(Layer::Tiles(TileLayer {
+                    name: child.attribute("name").unwrap_or_default().to_string(),
+                    width: attribute(&child, "width")?,
+                    height: attribute(&child, "height")?,
+                    visible: attribute_or(&child, "visible", 1)? != 0,
+                    data: decode_tile_data(
+                        data.attribute("encoding").unwrap_or("csv"),
+                        data.attribute("compression").unwrap_or_default(),
+                        data.text().unwrap_or_default())?,
+                    properties: tmx_properties(&child)?,
+                }));
+            },
+            "objectgroup" => {
+                let objects = child
+                    .children()
+                    .filter(|n| n.has_tag_name("object"))
+                    .map(|object| Ok(MapObject {
+                        id: attribute_or(&object, "id", 0)?,
+                        name: object.attribute("name").unwrap_or_default().to_string(),
+                        // `type` was renamed to `class` in Tiled 1.9.
+                        class: object.attribute("class").or_else(|| object.attribute("type")).unwrap_or_default().to_string(),
+                        x: attribute_or(&object, "x", 0.0)?,
+                        y: attribute_or(&object, "y", 0.0)?,
+                        width: attribute_or(&object, "width", 0.0)?,
+                        height: attribute_or(&object, "height", 0.0)?,
+                        gid: object.attribute("gid").map(|_| attribute(&object, "gid")).transpose()?,
+                        visible: attribute_or(&object, "visible", 1)? != 0,
+                        properties: tmx_properties(&object)?,
+                    }))
+                    .collect::<Result<Vec<MapObject>>>()?;
+                layers.push(Layer::Objects(ObjectLayer {
+                    name: child.attribute("name").unwrap_or_default().to_string(),
+                    visible: attribute_or(&child, "visible", 1)? != 0,
+                    objects,
+                    properties: tmx_properties(&child)?,
+                }));
+            },
+            "group" => tmx_layers(&child, layers)?,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn parse_tmx(text: &str, load_external: &dyn Fn(&str) -> Result<String>) -> Result<TileMap> {
+    let document = Document::parse(text).map_err(|error| map_error(error.to_string()))?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+        return Err(map_error("not a TMX map"));
+    }
+    if map.attribute("orientation").unwrap_or("orthogonal") != "orthogonal" {
+        return Err(map_error("only orthogonal maps are supported"));
+    }
+
+    let mut tilesets = Vec::new();
+    for node in map.children().filter(|n| n.has_tag_name("tileset")) {
+        let first_gid = attribute(&node, "firstgid")?;
+        let tileset = match node.attribute("source") {
+            Some(source) => {
+                let external = load_external(source)?;
+                let document = Document::parse(&external).map_err(|error| map_error(error.to_string()))?;
+                tmx_tileset(&document.root_element(), first_gid)?
+            },
+            None => tmx_tileset(&node, first_gid)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    tmx_layers(&map, &mut layers)?;
+
+    check_tile_sizes(TileMap {
+        width: attribute(&map, "width")?,
+        height: attribute(&map, "height")?,
+        tile_width: attribute(&map, "tilewidth")?,
+        tile_height: attribute(&map, "tileheight")?,
+        tilesets,
+        layers,
+        properties: tmx_properties(&map)?,
+    })
+}
+
+// JSON parsing
+
+fn json_u32(value: &Value, name: &str) -> Result<u32> {
+    value[name]
+        .as_u64()
+        .map(|number| number as u32)
+        .ok_or_else(|| map_error(format!("`{}` is missing or invalid", name)))
+}
+
+fn json_u32_or(value: &Value, name: &str, default: u32) -> u32 {
+    value[name].as_u64().map_or(default, |number| number as u32)
+}
+
+fn json_f32_or(value: &Value, name: &str, default: f32) -> f32 {
+    value[name].as_f64().map_or(default, |number| number as f32)
+}
+
+fn json_str<'v>(value: &'v Value, name: &str) -> &'v str {
+    value[name].as_str().unwrap_or_default()
+}
+
+fn json_properties(value: &Value) -> Result<Properties> {
+    let mut properties = Properties::new();
+    for property in value["properties"].as_array().into_iter().flatten() {
+        let kind = property["type"].as_str().unwrap_or("string");
+        let value = match &property["value"] {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        properties.insert(json_str(property, "name").to_string(), parse_property(kind, &value)?);
+    }
+    Ok(properties)
+}
+
+fn json_tileset(value: &Value, first_gid: u32) -> Result<Tileset> {
+    if value["image"].as_str().is_none() {
+        return Err(map_error("tilesets without a single image are not supported"));
+    }
+    Ok(Tileset {
+        first_gid,
+        name: json_str(value, "name").to_string(),
+        tile_width: json_u32(value, "tilewidth")?,
+        tile_height: json_u32(value, "tileheight")?,
+        tile_count: json_u32(value, "tilecount")?,
+        columns: json_u32(value, "columns")?,
+        spacing: json_u32_or(value, "spacing", 0),
+        margin: json_u32_or(value, "margin", 0),
+        image: json_str(value, "image").to_string(),
+        image_width: json_u32_or(value, "imagewidth", 0),
+        image_height: json_u32_or(value, "imageheight", 0),
+    })
+}
+
+fn json_layers(value: &Value, layers: &mut Vec<Layer>) -> Result<()> {
+    for layer in value["layers"].as_array().into_iter().flatten() {
+        let name = json_str(layer, "name").to_string();
+        let visible = layer["visible"].as_bool().unwrap_or(true);
+
+        match json_str(layer, "type") {
+            "tilelayer" => {
+                if layer["chunks"].is_array() {
+                    return Err(map_error("infinite maps are not supported"));
+                }
+                let data = match &layer["data"] {
+                    Value::String(text) => decode_tile_data(
+                        json_str(layer, "encoding"),
+                        json_str(layer, "compression"),
+                        text)?,
+                    Value::Array(gids) => gids
+                        .iter()
+                        .map(|gid| gid.as_u64().map(|gid| gid as u32).ok_or_else(|| map_error("invalid tile data")))
+                        .collect::<Result<Vec<u32>>>()?,
+                    _ => return Err(map_error("tile layer without data")),
+                };
+                layers.push(Layer::Tiles(TileLayer {
+                    name,
+                    width: json_u32(layer, "width")?,
+                    height: json_u32(layer, "height")?,
+                    visible,
+                    data,
+                    properties: json_properties(layer)?,
+                }));
+            },
+            "objectgroup" => {
+                let objects = layer["objects"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|object| Ok(MapObject {
+                        id: json_u32_or(object, "id", 0),
+                        name: json_str(object, "name").to_string(),
+                        class: object["class"].as_str().unwrap_or(json_str(object, "type")).to_string(),
+                        x: json_f32_or(object, "x", 0.0),
+                        y: json_f32_or(object, "y", 0.0),
+                        width: json_f32_or(object, "width", 0.0),
+                        height: json_f32_or(object, "height", 0.0),
+                        gid: object["gid"].as_u64().map(|gid| gid as u32),
+                        visible: object["visible"].as_bool().unwrap_or(true),
+                        properties: json_properties(object)?,
+                    }))
+                    .collect::<Result<Vec<MapObject>>>()?;
+                layers.push(Layer::Objects(ObjectLayer {
+                    name,
+                    visible,
+                    objects,
+                    properties: json_properties(layer)?,
+                }));
+            },
+            "group" => json_layers(layer, layers)?,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn parse_json(text: &str, load_external: &dyn Fn(&str) -> Result<String>) -> Result<TileMap> {
+    let map: Value = serde_json::from_str(text).map_err(|error| map_error(error.to_string()))?;
+    if json_str(&map, "orientation") != "orthogonal" {
+        return Err(map_error("only orthogonal maps are supported"));
+    }
+
+    let mut tilesets = Vec::new();
+    for tileset in map["tilesets"].as_array().into_iter().flatten() {
+        let first_gid = json_u32(tileset, "firstgid")?;
+        let tileset = match tileset["source"].as_str() {
+            Some(source) => {
+                let external = load_external(source)?;
+                let value: Value = serde_json::from_str(&external).map_err(|error| map_error(error.to_string()))?;
+                json_tileset(&value, first_gid)?
+            },
+            None => json_tileset(tileset, first_gid)?,
+        };
+        tilesets.push(tileset);
+    }
+
+    let mut layers = Vec::new();
+    json_layers(&map, &mut layers)?;
+
+    check_tile_sizes(TileMap {
+        width: json_u32(&map, "width")?,
+        height: json_u32(&map, "height")?,
+        tile_width: json_u32(&map, "tilewidth")?,
+        tile_height: json_u32(&map, "tileheight")?,
+        tilesets,
+        layers,
+        properties: json_properties(&map)?,
+    })
+}
+
+// zero sized tiles would divide by zero when drawing.
+fn check_tile_sizes(map: TileMap) -> Result<TileMap> {
+    if map.tile_width == 0 || map.tile_height == 0 {
+        return Err(map_error("the map has a zero tile width or height"));
+    }
+    if let Some(tileset) = map.tilesets.iter().find(|tileset| tileset.tile_width == 0 || tileset.tile_height == 0) {
+        return Err(map_error(format!("tileset `{}` has a zero tile width or height", tileset.name)));
+    }
+    Ok(map)
+}
+
+fn no_external_tilesets(source: &str) -> Result<String> {
+    Err(map_error(format!("external tileset `{}` needs TileMap::load or embedding", source)))
+}
+
+impl TileMap {
+    // maps using external tilesets need load, or the tileset embedded in the map.
+    pub fn from_tmx(text: &str) -> Result<Self> {
+        parse_tmx(text, &no_external_tilesets)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        parse_json(text, &no_external_tilesets)
+    }
+
+    // picks the format from the extension, .tmx or .json, and reads external
+    // tilesets relative to the map.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let load_external = |source: &str| -> Result<String> {
+            Ok(fs::read_to_string(directory.join(source))?)
+        };
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tmx") => parse_tmx(&text, &load_external),
+            Some("json") | Some("tmj") => parse_json(&text, &load_external),
+            _ => Err(map_error("unknown map format, expected .tmx or .json")),
+        }
+    }
+
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.get(name)
+    }
+
+    pub fn tile_layers(&self) -> impl Iterator<Item = &TileLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Tiles(layer) => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            Layer::Objects(layer) => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.object_layers().flat_map(|layer| layer.objects.iter())
+    }
+
+    pub fn find_object(&self, name: &str) -> Option<&MapObject> {
+        self.objects().find(|object| object.name == name)
+    }
+
+    // resolves a global tile id, with or without flip flags.
+    pub fn tile(&self, gid: u32) -> Option<Tile> {
+        let id = gid & GID_MASK;
+        if id == 0 {
+            return None;
+        }
+        let tileset = self.tilesets.iter().rposition(|tileset| tileset.first_gid <= id)?;
+        Some(Tile {
+            tileset,
+            id: id - self.tilesets[tileset].first_gid,
+            flip_horizontal: gid & FLIPPED_HORIZONTALLY != 0,
+            flip_vertical: gid & FLIPPED_VERTICALLY != 0,
+            flip_diagonal: gid & FLIPPED_DIAGONALLY != 0,
+        })
+    }
+
+    pub fn tile_at(&self, layer: &TileLayer, x: u32, y: u32) -> Option<Tile> {
+        if x >= layer.width || y >= layer.height {
+            return None;
+        }
+        self.tile(*layer.data.get((y * layer.width + x) as usize)?)
+    }
+
+    // creates a Position + Renderable entity per tile of every visible tile
+    // layer, using the layer index as z. texture_ids holds the texture of
+    // each tileset, whose frames must come from Tileset::frames. flipped and
+    // rotated tiles also get a Transform, placed by the map's tile size.
+    pub fn spawn_tiles<IR: ImageResource + 'static>(&self, world: &mut World, texture_ids: &[IR::TextureId]) -> Result<Vec<Entity>>
+        where <IR as ImageResource>::TextureId: Send + Sync {
+
+        if self.width > 256 || self.height > 256 || self.layers.len() > 256 {
+            return Err(map_error("maps spawned as Position entities are limited to 256x256 tiles and 256 layers"));
+        }
+
+        let mut entities = Vec::new();
+        for (z, layer) in self.layers.iter().enumerate() {
+            let layer = match layer {
+                Layer::Tiles(layer) if layer.visible => layer,
+                _ => continue,
+            };
+            for y in 0..layer.height {
+                for x in 0..layer.width {
+                    let tile = match self.tile_at(layer, x, y) {
+                        Some(tile) => tile,
+                        None => continue,
+                    };
+                    let texture_id = texture_ids.get(tile.tileset).ok_or(Error::TextureNotFound)?;
+                    let mut builder = world
+                        .create_entity()
+                        .with(Position { x: x as u8, y: y as u8, z: z as u8 })
+                        .with(Renderable::<IR>::new_frame(texture_id.clone(), tile.id as usize));
+                    if tile.is_transformed() {
+                        let (tile_x, tile_y) = ((x * self.tile_width) as f32, (y * self.tile_height) as f32);
+                        builder = builder.with(tile.transform(tile_x, tile_y, z as u8));
+                    }
+                    entities.push(builder.build());
+                }
+            }
+        }
+        Ok(entities)
+    }
+
+    // draws one tile layer directly, skipping tiles outside the screen. x and y
+    // are the world position of the map, the camera offset is applied.
+    pub fn draw_layer<IR: ImageResource>(&self, render_context: &mut RenderContext<IR>, layer: &TileLayer, texture_ids: &[IR::TextureId], x: i32, y: i32) {
+        let (offset_x, offset_y) = render_context.view_offset;
+        let origin_x = x - offset_x;
+        let origin_y = y - offset_y;
+        let tile_width = self.tile_width as i32;
+        let tile_height = self.tile_height as i32;
+
+        let first_column = ((-origin_x) / tile_width).max(0) as u32;
+        let first_row = ((-origin_y) / tile_height).max(0) as u32;
+        let last_column = ((render_context.screen_width as i32 - origin_x) / tile_width + 1).clamp(0, layer.width as i32) as u32;
+        let last_row = ((render_context.screen_height as i32 - origin_y) / tile_height + 1).clamp(0, layer.height as i32) as u32;
+
+        for row in first_row..last_row {
+            for column in first_column..last_column {
+                let tile = match self.tile_at(layer, column, row) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                let texture_id = match texture_ids.get(tile.tileset) {
+                    Some(texture_id) => texture_id.clone(),
+                    None => continue,
+                };
+                let tile_x = origin_x + column as i32 * tile_width;
+                let tile_y = origin_y + row as i32 * tile_height;
+                if tile.is_transformed() {
+                    let transform = tile.transform(tile_x as f32, tile_y as f32, 0);
+                    render_context.draw_ex(texture_id, Some(tile.id as usize), &transform);
+                } else {
+                    render_context.draw_frame(texture_id, tile.id as usize, tile_x, tile_y);
+                }
+            }
+        }
+    }
+
+    pub fn draw<IR: ImageResource>(&self, render_context: &mut RenderContext<IR>, texture_ids: &[IR::TextureId], x: i32, y: i32) {
+        for layer in self.tile_layers().filter(|layer| layer.visible) {
+            self.draw_layer(render_context, layer, texture_ids, x, y);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16">
+ <properties>
+  <property name="music" value="field"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="16" tileheight="16" tilecount="4" columns="2" spacing="1" margin="1">
+  <image source="ground.png" width="35" height="35"/>
+ </tileset>
+ <tileset firstgid="5" name="props" tilewidth="16" tileheight="16" tilecount="2" columns="2">
+  <image source="props.png" width="32" height="16"/>
+ </tileset>
+ <layer id="1" name="floor" width="2" height="2">
+  <data encoding="csv">
+1,2,
+2147483651,5
+</data>
+ </layer>
+ <group name="above">
+  <layer id="2" name="overlay" width="2" height="2" visible="0">
+   <data encoding="base64">AQAAAAIAAIAAAAAAAwAAQA==</data>
+  </layer>
+ </group>
+ <objectgroup name="things">
+  <object id="3" name="start" type="spawn" x="8" y="24">
+   <properties>
+    <property name="facing" type="int" value="2"/>
+    <property name="boss" type="bool" value="true"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>"#;
+
+    const JSON: &str = r#"{
+        "orientation": "orthogonal", "width": 2, "height": 1, "tilewidth": 8, "tileheight": 8,
+        "properties": [{ "name": "gravity", "type": "float", "value": 9.5 }],
+        "tilesets": [{
+            "firstgid": 1, "name": "tiles", "tilewidth": 8, "tileheight": 8, "tilecount": 4,
+            "columns": 4, "image": "tiles.png", "imagewidth": 32, "imageheight": 8
+        }],
+        "layers": [
+            { "type": "tilelayer", "name": "floor", "width": 2, "height": 1, "data": [4, 1073741825] },
+            { "type": "tilelayer", "name": "packed", "width": 2, "height": 1, "encoding": "base64", "data": "AgAAAAAAAAA=" },
+            { "type": "objectgroup", "name": "things", "objects": [
+                { "id": 1, "name": "door", "class": "exit", "x": 4, "y": 0, "width": 8, "height": 8, "visible": false }
+            ] }
+        ]
+    }"#;
+
+    fn tile_layer(map: &TileMap, name: &str) -> TileLayer {
+        map.tile_layers().find(|layer| layer.name == name).cloned().unwrap()
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode_base64("dGlsZWQ=").unwrap(), b"tiled");
+        assert_eq!(decode_base64(" dGls\n ZWQ ").unwrap(), b"tiled");
+        assert!(decode_base64("dGl*").is_err());
+    }
+
+    #[test]
+    fn decodes_csv_and_base64_layers() {
+        let gids = vec![1, 0x8000_0002, 0, 0x4000_0003];
+        assert_eq!(decode_tile_data("csv", "", "1,2147483650,\n0,1073741827\n").unwrap(), gids);
+        assert_eq!(decode_tile_data("base64", "", "AQAAAAIAAIAAAAAAAwAAQA==").unwrap(), gids);
+        assert!(decode_tile_data("csv", "", "1,x").is_err());
+        assert!(decode_tile_data("base64", "zlib", "AQAAAA==").is_err());
+        assert!(decode_tile_data("hex", "", "01").is_err());
+    }
+
+    #[test]
+    fn resolves_tiles_and_flip_flags() {
+        let map = TileMap::from_tmx(TMX).unwrap();
+
+        assert_eq!(map.tile(0), None);
+        assert_eq!(map.tile(0x8000_0000), None);
+        assert_eq!(map.tile(2), Some(Tile { tileset: 0, id: 1, flip_horizontal: false, flip_vertical: false, flip_diagonal: false }));
+        assert_eq!(map.tile(6), Some(Tile { tileset: 1, id: 1, flip_horizontal: false, flip_vertical: false, flip_diagonal: false }));
+        assert_eq!(map.tile(0x8000_0003), Some(Tile { tileset: 0, id: 2, flip_horizontal: true, flip_vertical: false, flip_diagonal: false }));
+        assert_eq!(map.tile(0x4000_0001), Some(Tile { tileset: 0, id: 0, flip_horizontal: false, flip_vertical: true, flip_diagonal: false }));
+        assert_eq!(map.tile(0xC000_0005), Some(Tile { tileset: 1, id: 0, flip_horizontal: true, flip_vertical: true, flip_diagonal: false }));
+        assert_eq!(map.tile(0x2000_0001), Some(Tile { tileset: 0, id: 0, flip_horizontal: false, flip_vertical: false, flip_diagonal: true }));
+    }
+
+    #[test]
+    fn turns_diagonal_flips_into_rotations() {
+        let map = TileMap::from_tmx(TMX).unwrap();
+        let transform = |gid: u32| {
+            let transform = map.tile(gid).unwrap().transform(16.0, 32.0, 1);
+            (transform.rotation, transform.flip_horizontal, transform.flip_vertical)
+        };
+
+        assert_eq!(transform(1), (0.0, false, false));
+        assert_eq!(transform(0xC000_0001), (0.0, true, true));
+        // Tiled's rotate right is diagonal + horizontal, rotate left diagonal + vertical.
+        assert_eq!(transform(0xA000_0001), (90.0, false, false));
+        assert_eq!(transform(0x6000_0001), (270.0, false, false));
+        assert_eq!(transform(0x2000_0001), (90.0, false, true));
+        assert_eq!(transform(0xE000_0001), (90.0, true, false));
+        assert!(!map.tile(1).unwrap().is_transformed());
+        assert!(map.tile(0x2000_0001).unwrap().is_transformed());
+    }
+
+    #[test]
+    fn parses_tmx() {
+        let map = TileMap::from_tmx(TMX).unwrap();
+
+        assert_eq!((map.width, map.height, map.tile_width, map.tile_height), (2, 2, 16, 16));
+        assert_eq!(map.property("music").and_then(PropertyValue::as_str), Some("field"));
+        assert_eq!(map.tilesets.len(), 2);
+        assert_eq!(map.tilesets[1].first_gid, 5);
+        assert_eq!(map.tilesets[0].frames()[3], AtlasFrame::new(18, 18, 16, 16));
+
+        let floor = tile_layer(&map, "floor");
+        assert_eq!(floor.data, vec![1, 2, 0x8000_0003, 5]);
+        assert_eq!(map.tile_at(&floor, 0, 1).map(|tile| tile.flip_horizontal), Some(true));
+        assert_eq!(map.tile_at(&floor, 1, 1).map(|tile| tile.tileset), Some(1));
+        assert_eq!(map.tile_at(&floor, 2, 0), None);
+
+        let overlay = tile_layer(&map, "overlay");
+        assert!(!overlay.visible);
+        assert_eq!(overlay.data, vec![1, 0x8000_0002, 0, 0x4000_0003]);
+
+        let start = map.find_object("start").unwrap();
+        assert_eq!(start.class, "spawn");
+        assert_eq!((start.x, start.y), (8.0, 24.0));
+        assert_eq!(start.property("facing").and_then(PropertyValue::as_int), Some(2));
+        assert_eq!(start.property("boss").and_then(PropertyValue::as_bool), Some(true));
+    }
+
+    #[test]
+    fn parses_json() {
+        let map = TileMap::from_json(JSON).unwrap();
+
+        assert_eq!((map.width, map.height, map.tile_width, map.tile_height), (2, 1, 8, 8));
+        assert_eq!(map.property("gravity").and_then(PropertyValue::as_float), Some(9.5));
+        assert_eq!(map.tilesets[0].image, "tiles.png");
+
+        let floor = tile_layer(&map, "floor");
+        assert_eq!(map.tile_at(&floor, 0, 0).map(|tile| tile.id), Some(3));
+        assert_eq!(map.tile_at(&floor, 1, 0).map(|tile| tile.flip_vertical), Some(true));
+        assert_eq!(tile_layer(&map, "packed").data, vec![2, 0]);
+
+        let door = map.find_object("door").unwrap();
+        assert_eq!(door.class, "exit");
+        assert_eq!((door.width, door.height), (8.0, 8.0));
+        assert!(!door.visible);
+    }
+
+    #[test]
+    fn rejects_unsupported_maps() {
+        let isometric = TMX.replace("orthogonal", "isometric");
+        assert!(TileMap::from_tmx(&isometric).is_err());
+
+        let external = r#"<map orientation="orthogonal" width="1" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" source="tiles.tsx"/>
+</map>"#;
+        assert!(TileMap::from_tmx(external).is_err());
+
+        assert!(TileMap::from_tmx(&TMX.replace(r#"tilewidth="16" tileheight="16">"#, r#"tilewidth="0" tileheight="16">"#)).is_err());
+        assert!(TileMap::from_tmx(&TMX.replace(r#"name="props" tilewidth="16""#, r#"name="props" tilewidth="0""#)).is_err());
+        assert!(TileMap::from_json(&JSON.replace(r#""tileheight": 8,"#, r#""tileheight": 0,"#)).is_err());
+    }
+}
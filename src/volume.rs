@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::Path;
+use crate::error::Result;
+use crate::settings_file::parse_settings;
+
+// player-facing volume levels, 0.0 to 1.0. the effective volume of a sound
+// is master times its bus times its own volume.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+    pub muted: bool,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        VolumeSettings {
+            master: 1.0,
+            music: 1.0,
+            effects: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl VolumeSettings {
+    pub fn music_level(&self) -> f32 {
+        if self.muted { 0.0 } else { self.master * self.music }
+    }
+
+    pub fn effects_level(&self) -> f32 {
+        if self.muted { 0.0 } else { self.master * self.effects }
+    }
+
+    // reads `Name = value` lines, as written by to_text. missing lines keep
+    // their default and unknown ones are ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut settings = VolumeSettings::default();
+        parse_settings(text, |name, value| {
+            let level = || value.parse::<f32>().map(|level| level.clamp(0.0, 1.0)).map_err(|_| "invalid volume");
+            match name {
+                "Master" => settings.master = level()?,
+                "Music" => settings.music = level()?,
+                "Effects" => settings.effects = level()?,
+                "Mute" => settings.muted = value.parse().map_err(|_| "expected true or false")?,
+                // settings from newer versions are skipped, so the others are kept.
+                _ => {},
+            }
+            Ok(())
+        })?;
+        Ok(settings)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // falls back to full volume if the file is missing or broken.
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        Self::load(path).unwrap_or_default()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, self.to_text())?;
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "Master = {}\nMusic = {}\nEffects = {}\nMute = {}\n",
+            self.master, self.music, self.effects, self.muted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_text() {
+        let settings = VolumeSettings { master: 0.5, music: 0.25, effects: 1.0, muted: true };
+        assert_eq!(VolumeSettings::parse(&settings.to_text()).unwrap(), settings);
+    }
+
+    #[test]
+    fn keeps_known_settings_next_to_unknown_ones() {
+        let settings = VolumeSettings::parse("# saved by a newer version\nMaster = 0.5\nVoice = 0.8\nMusic = 2\n").unwrap();
+        assert_eq!(settings, VolumeSettings { master: 0.5, music: 1.0, ..VolumeSettings::default() });
+        assert!(VolumeSettings::parse("Master = loud").is_err());
+        assert!(VolumeSettings::parse("Master").is_err());
+    }
+}